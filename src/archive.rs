@@ -0,0 +1,235 @@
+//! A long-lived handle over a packed file.
+//! The file is opened and its `ToC` is read only once, so that any number of
+//! lookups can be done afterwards without paying for it again.
+use std::fs::OpenOptions;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use crate::error::Result;
+use crate::metadata::Metadata;
+use crate::readers::{self, Entry, RecordReader, VersionedUnpacker};
+use crate::slice_archive::SliceArchive;
+use crate::utils::Record;
+
+/**
+An opened packed file, whatever its (supported) version is.
+
+# Usage.
+
+```
+use easypack::*;
+# use std::path::PathBuf;
+# use std::str::FromStr;
+
+# let packed_data_file = PathBuf::from_str("/tmp/__archive_doc__.bin").unwrap();
+pack_records(
+    &packed_data_file,
+    [
+        Record::new("c1".into(), vec![0x12, 0x34]),
+        Record::new("c2".into(), vec![0x34]),
+    ]
+    .into_iter(),
+).unwrap();
+
+let mut archive = Archive::open(&packed_data_file).unwrap();
+assert_eq!(archive.len(), 2);
+assert!(archive.contains("c1"));
+assert!(!archive.contains("nope"));
+assert_eq!(archive.names().collect::<Vec<_>>(), ["c1", "c2"]);
+
+let record = archive.get("c2").unwrap().unwrap();
+assert_eq!(record.data, vec![0x34]);
+assert!(archive.get("nope").unwrap().is_none());
+# std::fs::remove_file(&packed_data_file).unwrap();
+```
+*/
+pub struct Archive {
+    unpacker: Box<dyn VersionedUnpacker + Send>,
+}
+
+impl Archive {
+    /// Open the packed file `infile`, and read its `ToC`.
+    /// # Errors
+    /// Any IO error, or if the file is not a valid packed file.
+    pub fn open(infile: impl AsRef<Path>) -> Result<Self> {
+        let infile = OpenOptions::new().create(false).read(true).open(&infile)?;
+        Self::from_reader(BufReader::new(infile))
+    }
+
+    /// Use `reader` as the packed data, and read its `ToC`.
+    /// # Errors
+    /// Any IO error, or if the data is not valid packed data.
+    pub fn from_reader<R: Read + Seek + Send + 'static>(reader: R) -> Result<Self> {
+        let mut unpacker = readers::get_unpacker(reader)?;
+        unpacker.init()?;
+        Ok(Self { unpacker })
+    }
+
+    /// Use `data` as the packed data, e.g. from `include_bytes!`, and read its
+    /// `ToC`. The records are then borrowed from `data`, see `SliceArchive`.
+    /// # Errors
+    /// If the data is not valid packed data.
+    pub fn from_bytes(data: &[u8]) -> Result<SliceArchive<'_>> {
+        SliceArchive::new(data)
+    }
+
+    /// Read the record associated with `name`, if any.
+    /// # Errors
+    /// Any IO error, or if the data is not valid packed data.
+    pub fn get(&mut self, name: &str) -> Result<Option<Record>> {
+        self.unpacker.read_record(name)
+    }
+
+    /// Get a reader over the record associated with `name`, if any.
+    /// Nothing is read until the returned reader is used, so that big records
+    /// can be streamed, or only partially read.
+    /// # Errors
+    /// Any IO error.
+    pub fn open_record(&mut self, name: &str) -> Result<Option<RecordReader<'_>>> {
+        self.unpacker.open_record(name)
+    }
+
+    /// Read `len` bytes of the record associated with `name`, if any, starting
+    /// at `offset` in the record. Only these bytes are read, unless the record
+    /// is compressed.
+    /// # Errors
+    /// Any IO error, or an `EasypackError::InvalidRange` if the range does
+    /// not fit in the record.
+    pub fn get_range(&mut self, name: &str, offset: u64, len: usize) -> Result<Option<Vec<u8>>> {
+        self.unpacker.read_record_range(name, offset, len)
+    }
+
+    #[must_use]
+    /// Whether there is a record associated with `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.unpacker.contains(name)
+    }
+
+    #[must_use]
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.unpacker.len()
+    }
+
+    #[must_use]
+    /// Whether there are no records at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The names of all the records, in the order they are stored.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.unpacker.names()
+    }
+
+    /// The entries of all the records, in the order they are stored.
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.unpacker.toc().iter()
+    }
+
+    #[must_use]
+    /// The metadata of the archive, empty for the versions before 1.6.
+    pub fn metadata(&self) -> &Metadata {
+        self.unpacker.toc().metadata()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test::Tempfile;
+    use crate::writers;
+
+    use std::io::{BufWriter, Cursor};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    #[test]
+    /// The archive can be used with every supported version.
+    fn archive_all_versions() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        // The same records, packed with every version.
+        macro_rules! packed {
+            ($($version: ident),*) => {
+                [$({
+                    let mut buff = Cursor::new(vec![]);
+                    {
+                        let buffwriter = BufWriter::new(&mut buff);
+                        let mut writer =
+                            writers::$version::Packer::from_writer(buffwriter).write_header()?;
+                        writer.write_record(Record::new("file_1".to_owned(), vec![0x12, 0x34]))?;
+                        writer.write_record(Record::new("file_2".to_owned(), vec![0x56]))?;
+                        writer.close()?;
+                    }
+                    buff
+                }),*]
+            };
+        }
+        let buffs = packed!(ver_1_0, ver_1_1, ver_1_2, ver_1_3, ver_1_4, ver_1_5, ver_1_6, ver_1_7);
+
+        for buff in &buffs {
+            let archive = Archive::from_bytes(buff.get_ref())?;
+            assert_eq!(archive.len(), 2);
+            assert!(!archive.is_empty());
+            assert!(archive.contains("file_1"));
+            assert!(!archive.contains("file_3"));
+            assert_eq!(archive.names().collect::<Vec<_>>(), ["file_1", "file_2"]);
+            assert_eq!(
+                archive.get("file_1")?.as_deref(),
+                Some([0x12, 0x34].as_slice())
+            );
+            assert_eq!(archive.get("file_2")?.as_deref(), Some([0x56].as_slice()));
+            assert!(archive.get("file_3")?.is_none());
+        }
+
+        for buff in buffs {
+            let mut archive = Archive::from_reader(buff)?;
+            assert_eq!(archive.len(), 2);
+            assert!(!archive.is_empty());
+            assert!(archive.contains("file_1"));
+            assert!(archive.contains("file_2"));
+            assert!(!archive.contains("file_3"));
+            assert_eq!(archive.names().collect::<Vec<_>>(), ["file_1", "file_2"]);
+            let sizes: Vec<_> = archive
+                .entries()
+                .map(|entry| (entry.data_start, entry.data_len))
+                .collect();
+            assert_eq!(sizes, [(6, 2), (8, 1)]);
+            assert_eq!(archive.get("file_1")?.unwrap().data, vec![0x12, 0x34]);
+            assert_eq!(archive.get("file_2")?.unwrap().data, vec![0x56]);
+            // Lookups can be repeated, the archive is still usable.
+            assert_eq!(archive.get("file_1")?.unwrap().data, vec![0x12, 0x34]);
+            assert!(archive.get("file_3")?.is_none());
+            assert_eq!(archive.get_range("file_1", 1, 1)?, Some(vec![0x34]));
+            assert!(archive.get_range("file_2", 1, 1).is_err());
+
+            let mut data = vec![];
+            archive
+                .open_record("file_1")?
+                .unwrap()
+                .read_to_end(&mut data)?;
+            assert_eq!(data, vec![0x12, 0x34]);
+            assert!(archive.open_record("file_3")?.is_none());
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// An archive can be opened from a file.
+    fn archive_from_file() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/archive_from_file.bin")?);
+        crate::pack_records(&*packed_file, std::iter::empty())?;
+
+        let archive = Archive::open(&*packed_file)?;
+        assert!(archive.is_empty());
+        assert_eq!(archive.names().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    /// Opening something which is not a packed file fails.
+    fn archive_invalid() {
+        assert!(Archive::from_reader(Cursor::new(b"This is just text.".to_vec())).is_err());
+        assert!(Archive::from_bytes(b"This is just text.").is_err());
+        assert!(Archive::from_bytes(b"SMPL\x01\x03").is_err());
+    }
+}
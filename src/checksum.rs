@@ -0,0 +1,93 @@
+//! The checksums used to verify the integrity of the records.
+//! Implemented in here, to keep the crate free of dependencies.
+
+/// The CRC-32 (IEEE 802.3) lookup table, built at compile time.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        #[allow(clippy::cast_possible_truncation)]
+        // i is always < 256.
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// A CRC-32 (IEEE 802.3), which can be computed incrementally.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    #[must_use]
+    /// Start a new checksum.
+    pub const fn new() -> Self {
+        Self { state: u32::MAX }
+    }
+
+    /// Add `data` to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            let i = (self.state ^ u32::from(*byte)) & 0xFF;
+            self.state = CRC32_TABLE[i as usize] ^ (self.state >> 8);
+        }
+    }
+
+    #[must_use]
+    /// The checksum of all the data added so far.
+    pub const fn finish(self) -> u32 {
+        self.state ^ u32::MAX
+    }
+}
+
+#[must_use]
+/// The CRC-32 of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Check against well known values.
+    fn crc32_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+
+    #[test]
+    /// Computing the checksum a piece at a time gives the same result.
+    fn crc32_incremental() {
+        let mut crc = Crc32::new();
+        crc.update(b"The quick brown ");
+        crc.update(b"");
+        crc.update(b"fox jumps over the lazy dog");
+        assert_eq!(crc.finish(), 0x414F_A339);
+    }
+}
@@ -0,0 +1,245 @@
+//! The built-in codec: LZ77, with the block layout of LZ4.
+//! It only needs `alloc`, so it is always available.
+//!
+//! The compressed data is a list of sequences, each one made of
+//! - a token: the high 4 bits are the number of literals, the low 4 bits the
+//!   length of the match minus 4; if any is 15, more bytes follow
+//! - the extra bytes of the number of literals, if any: they are added up,
+//!   and the last one is the first which is not 255
+//! - the literals, copied as they are
+//! - u16 (2 bytes) how far back the match starts, never 0
+//! - the extra bytes of the length of the match, as for the literals
+//!
+//! The last sequence has literals only, and it ends the data.
+use alloc::borrow::ToOwned;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::{EasypackError, Result};
+
+// The shortest match worth encoding.
+const MIN_MATCH: usize = 4;
+// The farthest a match can start, since the offset is a u16.
+const MAX_OFFSET: usize = u16::MAX as usize;
+// The number of bits of the hash of 4 bytes, which is the index in the table
+// of the positions already seen.
+const HASH_BITS: u32 = 12;
+
+/// Compress `data`. This never fails, but incompressible data gets a bit
+/// bigger.
+#[must_use]
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    // The last position (plus one, so that 0 is none) with the same hash.
+    let mut table = vec![0_usize; 1 << HASH_BITS];
+    // Where the literals not written yet start.
+    let mut anchor = 0;
+    let mut pos = 0;
+    while pos + MIN_MATCH <= data.len() {
+        let slot = &mut table[hash(&data[pos..pos + MIN_MATCH])];
+        let candidate = slot.checked_sub(1);
+        *slot = pos + 1;
+        if let Some(candidate) = candidate.filter(|&c| {
+            pos - c <= MAX_OFFSET && data[c..c + MIN_MATCH] == data[pos..pos + MIN_MATCH]
+        }) {
+            let mut len = MIN_MATCH;
+            // The match may overlap with the data it copies, which is fine.
+            while pos + len < data.len() && data[candidate + len] == data[pos + len] {
+                len += 1;
+            }
+            write_sequence(&mut out, &data[anchor..pos], Some((pos - candidate, len)));
+            pos += len;
+            anchor = pos;
+        } else {
+            pos += 1;
+        }
+    }
+    write_sequence(&mut out, &data[anchor..], None);
+    out
+}
+
+/// Decompress `data`, which must give exactly `len` bytes.
+/// # Errors
+/// If the data is not valid, or its size is not `len`.
+pub fn decompress(data: &[u8], len: usize) -> Result<Vec<u8>> {
+    let mut out: Vec<u8> = Vec::new();
+    let mut pos = 0;
+    loop {
+        let token = *data.get(pos).ok_or_else(|| invalid("missing sequence"))?;
+        pos += 1;
+
+        let mut lit_len = usize::from(token >> 4);
+        if lit_len == 15 {
+            lit_len = extra_len(data, &mut pos, lit_len)?;
+        }
+        let literals = pos
+            .checked_add(lit_len)
+            .and_then(|end| data.get(pos..end))
+            .ok_or_else(|| invalid("not enough literals"))?;
+        if out.len() + lit_len > len {
+            return Err(invalid("too much data"));
+        }
+        out.extend_from_slice(literals);
+        pos += lit_len;
+        if pos == data.len() {
+            break;
+        }
+
+        let offset = match data.get(pos..pos + 2) {
+            Some(&[lo, hi]) => usize::from(u16::from_le_bytes([lo, hi])),
+            _ => return Err(invalid("missing offset")),
+        };
+        pos += 2;
+        if offset == 0 || offset > out.len() {
+            return Err(invalid("offset out of the data"));
+        }
+        let mut match_len = usize::from(token & 0x0F);
+        if match_len == 15 {
+            match_len = extra_len(data, &mut pos, match_len)?;
+        }
+        match_len += MIN_MATCH;
+        if out.len() + match_len > len {
+            return Err(invalid("too much data"));
+        }
+        // Byte by byte, since the match may overlap with what it copies.
+        let start = out.len() - offset;
+        for i in start..start + match_len {
+            out.push(out[i]);
+        }
+    }
+    if out.len() != len {
+        return Err(invalid("not enough data"));
+    }
+    Ok(out)
+}
+
+// The hash of the 4 bytes in `bytes`.
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    // The top bits are the best mixed ones.
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+// The token holds lengths up to 14: longer ones overflow into extra bytes.
+#[allow(clippy::cast_possible_truncation)]
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], found: Option<(usize, usize)>) {
+    let lit_len = literals.len();
+    let match_len = found.map_or(0, |(_, len)| len - MIN_MATCH);
+    // Both are at most 15, so they fit in 4 bits each.
+    out.push(((lit_len.min(15) << 4) | match_len.min(15)) as u8);
+    if lit_len >= 15 {
+        write_extra_len(out, lit_len - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = found {
+        // Matches are never farther than `MAX_OFFSET`.
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15 {
+            write_extra_len(out, match_len - 15);
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_extra_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    // Less than 255 here.
+    out.push(len as u8);
+}
+
+// Add the extra bytes of a length to `len`.
+fn extra_len(data: &[u8], pos: &mut usize, mut len: usize) -> Result<usize> {
+    loop {
+        let byte = *data.get(*pos).ok_or_else(|| invalid("missing length"))?;
+        *pos += 1;
+        len = len
+            .checked_add(usize::from(byte))
+            .ok_or_else(|| invalid("length too big"))?;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+fn invalid(what: &str) -> EasypackError {
+    EasypackError::InvalidFileError("Compressed data is not valid: ".to_owned() + what)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Some data which does not repeat, without pulling in a random generator.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x1234_5678_u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state.to_le_bytes()[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Whatever the data, it is decompressed as it was.
+    fn lz_round_trip() -> Result<()> {
+        let mut text = Vec::new();
+        for i in 0..2_000 {
+            text.extend_from_slice(
+                alloc::format!("{{\"id\": {i}, \"name\": \"record\"}},").as_bytes(),
+            );
+        }
+        // Matches far away, right at the edge of the window.
+        let mut far = noise(MAX_OFFSET);
+        far.extend_from_within(..100);
+        far.extend_from_slice(&noise(10));
+
+        let inputs = [
+            vec![],
+            vec![0x12],
+            vec![0x12, 0x34, 0x56, 0x78],
+            vec![0x42; 5],
+            vec![0x42; 100_000],
+            b"abcabcabcabcabcabcabcxyzabcabc".to_vec(),
+            noise(1_000),
+            noise(100_000),
+            text,
+            far,
+        ];
+        for data in inputs {
+            let compressed = compress(&data);
+            assert_eq!(decompress(&compressed, data.len())?, data);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Repeated data gets much smaller.
+    fn lz_compresses() {
+        assert!(compress(&vec![0x42; 100_000]).len() < 1_000);
+        let text = b"The quick brown fox jumps over the lazy dog. ".repeat(100);
+        assert!(compress(&text).len() < text.len() / 10);
+    }
+
+    #[test]
+    /// Broken data is an error, not a panic.
+    fn lz_invalid() {
+        let data = b"abcabcabcabcabcabcabcxyzabcabc".repeat(20);
+        let compressed = compress(&data);
+        assert!(decompress(&compressed, data.len() - 1).is_err());
+        assert!(decompress(&compressed, data.len() + 1).is_err());
+        for len in 0..compressed.len() {
+            assert!(decompress(&compressed[..len], data.len()).is_err());
+        }
+        // An offset going back to before the start.
+        assert!(decompress(&[0x10, 0x12, 0x02, 0x00], 5).is_err());
+        assert!(decompress(&[0x10, 0x12, 0x00, 0x00], 5).is_err());
+        // A length which never ends.
+        assert!(decompress(&[0xF0, 0xFF, 0xFF], 1_000).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! The compression of the data of the records, since version 1.4.
+//! Every record is compressed on its own, so that it can be read without
+//! touching any other.
+//! The built-in method only needs `alloc`, while the others are behind the
+//! features with the same name.
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::error::{EasypackError, Result};
+
+mod lz;
+
+/// How much bigger than its compressed data a record can be, at most. The
+/// size of a record comes from the `ToC`, which can't be trusted: a bigger
+/// one is refused before decompressing anything, rather than trying to
+/// allocate it.
+/// None of the methods gets anywhere close to this, even on data which is
+/// all zeros (the worst case is zstd, at about 32768).
+const MAX_RATIO: usize = 1 << 16;
+
+/// How the data of a record is stored in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// As it is.
+    #[default]
+    None,
+    /// With the built-in LZ77 codec: fast, and always available.
+    Lz,
+    /// With deflate, which needs the `deflate` feature.
+    Deflate,
+    /// With zstd, which needs the `zstd` feature.
+    Zstd,
+}
+
+impl Compression {
+    #[must_use]
+    /// The code of the method, as stored in the `ToC`.
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Lz => 1,
+            Self::Deflate => 2,
+            Self::Zstd => 3,
+        }
+    }
+
+    /// The method with the given `code`, as stored in the `ToC`.
+    /// # Errors
+    /// If there is no such method.
+    pub fn from_code(code: u8) -> Result<Self> {
+        match code {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Lz),
+            2 => Ok(Self::Deflate),
+            3 => Ok(Self::Zstd),
+            _ => Err(EasypackError::InvalidFileError(format!(
+                "Unknown compression method {code}"
+            ))),
+        }
+    }
+
+    #[must_use]
+    /// Whether data can be compressed and decompressed with this method, in
+    /// this build.
+    pub const fn is_available(self) -> bool {
+        match self {
+            Self::None | Self::Lz => true,
+            Self::Deflate => cfg!(feature = "deflate"),
+            Self::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+
+    /// Compress `data`.
+    /// # Errors
+    /// If the method is not available in this build.
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Lz => Ok(lz::compress(data)),
+            #[cfg(feature = "deflate")]
+            Self::Deflate => {
+                use std::io::Write;
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(zstd::bulk::compress(data, 0)?),
+            #[cfg(not(feature = "deflate"))]
+            Self::Deflate => Err(self.unavailable()),
+            #[cfg(not(feature = "zstd"))]
+            Self::Zstd => Err(self.unavailable()),
+        }
+    }
+
+    /// Decompress `data`, which must give exactly `len` bytes.
+    /// # Errors
+    /// If the method is not available in this build, or the data is not
+    /// valid. If `len` is more than `MAX_RATIO` times the size of `data`.
+    pub fn decompress(self, data: &[u8], len: usize) -> Result<Vec<u8>> {
+        if self != Self::None && len > data.len().saturating_mul(MAX_RATIO) {
+            return Err(EasypackError::InvalidFileError(format!(
+                "Compressed data is not valid: {} bytes can't give {len} bytes",
+                data.len()
+            )));
+        }
+        let decompressed = match self {
+            Self::None => data.to_vec(),
+            Self::Lz => lz::decompress(data, len)?,
+            #[cfg(feature = "deflate")]
+            Self::Deflate => read_limited(flate2::read::DeflateDecoder::new(data), len)?,
+            #[cfg(feature = "zstd")]
+            Self::Zstd => read_limited(zstd::stream::read::Decoder::with_buffer(data)?, len)?,
+            #[cfg(not(feature = "deflate"))]
+            Self::Deflate => return Err(self.unavailable()),
+            #[cfg(not(feature = "zstd"))]
+            Self::Zstd => return Err(self.unavailable()),
+        };
+        if decompressed.len() != len {
+            return Err(EasypackError::InvalidFileError(format!(
+                "Compressed data is not valid: {} bytes, expected {len}",
+                decompressed.len()
+            )));
+        }
+        Ok(decompressed)
+    }
+
+    // The error for the methods which are not available in this build.
+    #[cfg(not(all(feature = "deflate", feature = "zstd")))]
+    fn unavailable(self) -> EasypackError {
+        EasypackError::UnsupportedCompression(format!(
+            "{self} is not available, is its feature enabled?"
+        ))
+    }
+}
+
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+// Read all the data from `decoder`, but never more than one byte over `len`,
+// which is enough to tell that there are too many.
+fn read_limited(decoder: impl std::io::Read, len: usize) -> Result<Vec<u8>> {
+    use std::io::Read;
+    let mut decompressed = Vec::new();
+    decoder
+        .take((len as u64).saturating_add(1))
+        .read_to_end(&mut decompressed)
+        .map_err(|e| {
+            EasypackError::InvalidFileError(format!("Compressed data is not valid: {e}"))
+        })?;
+    Ok(decompressed)
+}
+
+impl core::fmt::Display for Compression {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Lz => "lz",
+            Self::Deflate => "deflate",
+            Self::Zstd => "zstd",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    /// The codes in the `ToC` match the methods.
+    fn compression_codes() -> Result<()> {
+        for method in [
+            Compression::None,
+            Compression::Lz,
+            Compression::Deflate,
+            Compression::Zstd,
+        ] {
+            assert_eq!(Compression::from_code(method.code())?, method);
+        }
+        assert!(Compression::from_code(4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Every available method gives the data back, and the others fail.
+    fn compression_round_trip() -> Result<()> {
+        let data = b"Some text, some more text, and even more text.".repeat(10);
+        for method in [
+            Compression::None,
+            Compression::Lz,
+            Compression::Deflate,
+            Compression::Zstd,
+        ] {
+            if !method.is_available() {
+                assert!(matches!(
+                    method.compress(&data),
+                    Err(EasypackError::UnsupportedCompression(_))
+                ));
+                assert!(matches!(
+                    method.decompress(&data, data.len()),
+                    Err(EasypackError::UnsupportedCompression(_))
+                ));
+                continue;
+            }
+            let compressed = method.compress(&data)?;
+            assert_eq!(method.decompress(&compressed, data.len())?, data);
+            assert!(method.decompress(&compressed, data.len() + 1).is_err());
+            if method != Compression::None {
+                assert!(compressed.len() < data.len());
+                assert!(method.decompress(&[0xFF; 10], data.len()).is_err());
+            }
+        }
+        assert!(Compression::None.decompress(&[0x12], 2).is_err());
+        assert_eq!(
+            Compression::Lz.decompress(&Compression::Lz.compress(&[])?, 0)?,
+            vec![]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Data compressing as well as it gets is still below the ratio limit,
+    /// while sizes above it are refused before decompressing anything.
+    fn compression_ratio() -> Result<()> {
+        let data = vec![0; 16 << 20];
+        for method in [Compression::Lz, Compression::Deflate, Compression::Zstd] {
+            if !method.is_available() {
+                continue;
+            }
+            let compressed = method.compress(&data)?;
+            assert_eq!(method.decompress(&compressed, data.len())?, data);
+            for len in [compressed.len() * MAX_RATIO + 1, usize::MAX] {
+                assert!(matches!(
+                    method.decompress(&compressed, len),
+                    Err(EasypackError::InvalidFileError(_))
+                ));
+            }
+        }
+        assert!(Compression::None.decompress(&[0x12], usize::MAX).is_err());
+        Ok(())
+    }
+}
@@ -1,8 +1,15 @@
+use alloc::format;
+use alloc::string::String;
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 /// The errors that may occur.
+/// New variants may be added, and `IoError` is only there with the `std`
+/// feature: matching on it needs a wildcard arm.
+#[non_exhaustive]
 pub enum EasypackError {
     /// A generic IO Error
+    #[cfg(feature = "std")]
     IoError(std::io::Error),
     /// When the input file is wrong and unreadable.
     InvalidFileError(String),
@@ -12,27 +19,44 @@ pub enum EasypackError {
     RecordNameTooBig(String),
     /// If the same record name is used twice.
     RecordSameName(String),
+    /// If the data of a record does not match its checksum.
+    ChecksumMismatch(String),
+    /// If a record name can't be used as a path, since it would end up
+    /// outside of the output directory.
+    UnsafeRecordName(String),
+    /// If a file can't be packed, since it is not a regular file or a
+    /// directory (e.g. a symlink).
+    UnsupportedFile(String),
+    /// If a record is compressed with a method which is not available, since
+    /// its feature is not enabled.
+    UnsupportedCompression(String),
+    /// If the metadata of a record can't be stored, since a key is too long or
+    /// there are too many pairs.
+    InvalidMetadata(String),
+    /// If a range of a record to be read does not fit in the record.
+    InvalidRange(String),
     /// Internal error.
     InternalError(String),
 }
 
-impl std::fmt::Display for EasypackError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for EasypackError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.write_str(&format!("{self:?}"))
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for EasypackError {
     fn from(e: std::io::Error) -> Self {
         Self::IoError(e)
     }
 }
-impl std::convert::From<std::num::TryFromIntError> for EasypackError {
-    fn from(e: std::num::TryFromIntError) -> Self {
+impl core::convert::From<core::num::TryFromIntError> for EasypackError {
+    fn from(e: core::num::TryFromIntError) -> Self {
         Self::InternalError(format!("{e}"))
     }
 }
 
-impl std::error::Error for EasypackError {}
+impl core::error::Error for EasypackError {}
 
-pub type Result<T> = std::result::Result<T, EasypackError>;
+pub type Result<T> = core::result::Result<T, EasypackError>;
@@ -1,5 +1,6 @@
 #![warn(clippy::nursery)]
 #![warn(clippy::pedantic)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 /*!
 # Easypack: a simple, no-dependencies data packer/unpacker.
@@ -16,7 +17,42 @@ exposed, the related Unpacker is not since we internally allow multiple
 versions to work. Thus, the API guarantees to always write the latest version,
 and unpack all the supported versions.
 
-
+When many records have to be read from the same file, the file can be opened
+once as an `Archive`, which keeps the table of contents in memory and can be
+used for any number of lookups.
+
+Packed data which is already in memory (e.g. from `include_bytes!`) can be
+read with `Archive::from_bytes`, borrowing the records from it. Packed data
+can be built in memory too, with `pack_records_to_vec`, and read back with
+`unpack_records_from_slice`.
+
+With the `mmap` feature, the file can be mapped in memory instead, as a
+`MmapArchive`: records are then borrowed straight from the mapped file,
+without any copy.
+
+Everything which needs the file system lives behind the `std` feature, which
+is enabled by default. Without it the crate is `no_std` (it only needs
+`alloc`), and packed data can still be read from memory with
+`SliceArchive::new`, whatever its version is.
+
+The data of every record can be compressed, choosing the method record by
+record with `Packer::write_compressed_record`: the built-in `Compression::Lz`
+is always there, while `Compression::Deflate` and `Compression::Zstd` need the
+`deflate` and `zstd` features. Records are decompressed when they are read.
+
+Every record can carry its metadata, as typed key/value pairs (see
+`Metadata`), with well-known keys for the modification time, the Unix
+permissions, and the content type. `pack_files` keeps the modification time
+and the permissions of the files (`pack_dir` only the permissions), and
+`unpack_files` and `unpack_all` restore them.
+
+The archive itself has its metadata too (e.g. a build ID, or a description),
+set with `Packer::set_metadata` and read with `read_archive_metadata`, without
+reading any record.
+*/
+#![cfg_attr(
+    feature = "std",
+    doc = r#"
 # Pack and unpdack data from file.
 
 In the following example, we can see how we can pack data in a single file, and retrieve some.
@@ -115,23 +151,63 @@ assert_eq!(dumped_content, "some bytes from content_1".to_owned());
 # std::fs::remove_file(&content_1).unwrap();
 # std::fs::remove_file(&content_2).unwrap();
 # std::fs::remove_file(&dumped).unwrap();
-*/
+"#
+)]
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
 use std::fs::OpenOptions;
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
 use std::io::{BufReader, BufWriter};
-use std::io::{Read, Write};
-use std::path::Path;
+#[cfg(feature = "std")]
+use std::path::{Component, Path, PathBuf};
 
+#[cfg(feature = "std")]
+mod archive;
+mod checksum;
+mod compression;
 mod error;
+mod metadata;
+#[cfg(feature = "mmap")]
+mod mmap;
 mod readers;
+mod slice_archive;
 mod utils;
+#[cfg(feature = "std")]
+mod verify;
+#[cfg(feature = "std")]
 mod writers;
 
+#[cfg(feature = "std")]
+pub use crate::archive::Archive;
+pub use crate::compression::Compression;
+pub use crate::error::EasypackError;
+#[cfg(feature = "std")]
 use crate::error::Result;
+pub use crate::metadata::{Metadata, MetadataValue};
+#[cfg(feature = "mmap")]
+pub use crate::mmap::MmapArchive;
+pub use crate::readers::Entry;
+#[cfg(feature = "std")]
+pub use crate::readers::RecordReader;
+pub use crate::slice_archive::SliceArchive;
+#[cfg(feature = "std")]
+use crate::utils::AtomicFile;
 pub use crate::utils::Record;
-pub use crate::writers::Packer;
-
+#[cfg(feature = "std")]
+pub use crate::verify::{verify, Problem};
+#[cfg(feature = "std")]
+use crate::writers::packer::PackFormat;
+#[cfg(feature = "std")]
+pub use crate::writers::{PackSummary, Packer, RecordWriter};
+
+#[cfg(feature = "std")]
 /// Pack the given `records` in the specified `outfile`.
+/// If `outfile` already exists, it is replaced as a whole, see
+/// `OpenMode::Truncate`.
 ///
 /// # Errors
 ///
@@ -140,20 +216,69 @@ pub fn pack_records(
     outfile: impl AsRef<Path>,
     records: impl Iterator<Item = Record>,
 ) -> Result<()> {
-    let outfile = OpenOptions::new().create(true).write(true).open(&outfile)?;
-    let bufwriter = BufWriter::new(outfile);
+    pack_records_with_mode(outfile, records, OpenMode::default())
+}
+
+#[cfg(feature = "std")]
+/// How the pack functions open their output file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Create the file, or replace it as a whole if it already exists.
+    #[default]
+    Truncate,
+    /// Create the file, and fail if it already exists.
+    CreateNew,
+}
+
+#[cfg(feature = "std")]
+/// Pack the given `records` in the specified `outfile`, which is opened as
+/// `mode` says.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors. With `OpenMode::CreateNew`,
+/// an `EasypackError::IoError` of kind `AlreadyExists` if `outfile` already
+/// exists.
+pub fn pack_records_with_mode(
+    outfile: impl AsRef<Path>,
+    records: impl Iterator<Item = Record>,
+    mode: OpenMode,
+) -> Result<()> {
+    let outfile = AtomicFile::create(outfile, mode)?;
+    let bufwriter = BufWriter::new(outfile.file());
 
     let mut writer = Packer::from_writer(bufwriter).write_header()?;
     for record in records {
         writer.write_record(record)?;
     }
     writer.close()?;
-    Ok(())
+    outfile.persist()
+}
+
+#[cfg(feature = "std")]
+/// Pack the given `records` in memory, e.g. to send them to another process or
+/// to store them in a database. The data is the same as `pack_records` would
+/// write in a file.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn pack_records_to_vec(records: impl Iterator<Item = Record>) -> Result<Vec<u8>> {
+    let mut writer = Packer::from_writer(vec![]).write_header()?;
+    for record in records {
+        writer.write_record(record)?;
+    }
+    let (data, _) = writer.close()?;
+    Ok(data)
 }
 
+#[cfg(feature = "std")]
 /// Pack the given `records` in the specified `outfile`, which already contains
 /// packed data. This operation is effectively an update.
 ///
+/// Only files written with the latest version can be updated: `compact` the
+/// older ones first, to upgrade them.
+///
 /// # Errors
 ///
 /// Check `EasyPackError` for the possible errors.
@@ -161,51 +286,174 @@ pub fn pack_records_update(
     outfile: impl AsRef<Path>,
     records: impl Iterator<Item = Record>,
 ) -> Result<()> {
-    let (old_toc, file_size, version) = {
-        let infile = OpenOptions::new().create(false).read(true).open(&outfile)?;
-        let file_size = infile
-            .metadata()
-            .expect("Unable to read the size of the file")
-            .len();
-
-        let mut bufreader = BufReader::new(infile);
-        let version = readers::read_header(&mut bufreader)?;
-        let mut unpacker = readers::get_unpacker(&mut bufreader)?;
-        // Init the unpacker, otherwise the Toc is empty
-        // XXX Bad, should do something to avoid the need to "remember" about
-        // this detail :)
-        unpacker.init()?;
-
-        let mut old_toc = vec![];
-        unpacker
-            .inspect_toc(&mut |pos, size, name| {
-                old_toc.push((*pos, *size, name.clone()));
-            })
-            .expect("Unable to read the toc?");
-        (old_toc, file_size, version)
-    };
-    let initial_toc: Vec<_> = old_toc
-        .into_iter()
-        .map(|(pos, size, name)| writers::TocEntry::new(name, pos, size))
-        .collect();
-    {
-        let outfile = OpenOptions::new()
-            .create(false)
-            .append(true)
-            .open(&outfile)?;
-        let bufwriter = BufWriter::new(outfile);
-        let mut packer = Packer::from_writer(bufwriter);
-        let mut writer = packer.append_mode(initial_toc, file_size, &version)?;
-        for record in records {
-            writer.write_record(record)?;
+    let (initial_toc, metadata, file_size, version) = read_toc_for_update(&outfile)?;
+    let outfile = OpenOptions::new()
+        .create(false)
+        .append(true)
+        .open(&outfile)?;
+    let bufwriter = BufWriter::new(outfile);
+    let mut packer = Packer::from_writer(bufwriter);
+    let mut writer = packer.append_mode(initial_toc, file_size, &version)?;
+    writer.set_metadata(metadata)?;
+    for record in records {
+        writer.write_record(record)?;
+    }
+    let (bufwriter, _) = writer.close()?;
+    sync_update(bufwriter)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Remove the records associated with the `names` from the specified
+/// `outfile`, which already contains packed data.
+///
+/// The data is not removed, but a new `ToC` without these records is written
+/// at the end of the file, as `pack_records_update` does.
+/// Only files written with the latest version can be updated, see
+/// `pack_records_update`.
+///
+/// # Returns
+///
+/// The names that were not found in the file. If none of the names is found,
+/// the file is not touched.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn remove_records<T: AsRef<str>>(
+    outfile: impl AsRef<Path>,
+    names: impl Iterator<Item = T>,
+) -> Result<Vec<String>> {
+    let (mut toc, metadata, file_size, version) = read_toc_for_update(&outfile)?;
+    let existing: std::collections::HashSet<_> = toc.iter().map(writers::TocEntry::name).collect();
+    let mut to_remove = std::collections::HashSet::new();
+    let mut notfound = vec![];
+    for name in names {
+        let name = name.as_ref();
+        if existing.contains(name) {
+            to_remove.insert(name.to_owned());
+        } else {
+            notfound.push(name.to_owned());
         }
-        writer.close()?;
     }
+    if to_remove.is_empty() {
+        return Ok(notfound);
+    }
+    toc.retain(|entry| !to_remove.contains(entry.name()));
+
+    let outfile = OpenOptions::new()
+        .create(false)
+        .append(true)
+        .open(&outfile)?;
+    let bufwriter = BufWriter::new(outfile);
+    let mut packer = Packer::from_writer(bufwriter);
+    let mut writer = packer.append_mode(toc, file_size, &version)?;
+    writer.set_metadata(metadata)?;
+    let (bufwriter, _) = writer.close()?;
+    sync_update(bufwriter)?;
+
+    Ok(notfound)
+}
+
+#[cfg(feature = "std")]
+/// Pack the given `records` in the specified `outfile`, which already contains
+/// packed data, replacing the records with the same names.
+///
+/// The old data is not removed, but the names are pointed to the new data,
+/// keeping their place in the `ToC`. The records whose name is not in the
+/// file yet are added, as `pack_records_update` does.
+/// Only files written with the latest version can be updated, see
+/// `pack_records_update`.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn replace_records(
+    outfile: impl AsRef<Path>,
+    records: impl Iterator<Item = Record>,
+) -> Result<()> {
+    let (initial_toc, metadata, file_size, version) = read_toc_for_update(&outfile)?;
+    let outfile = OpenOptions::new()
+        .create(false)
+        .append(true)
+        .open(&outfile)?;
+    let bufwriter = BufWriter::new(outfile);
+    let mut packer = Packer::from_writer(bufwriter);
+    let mut writer = packer.append_mode(initial_toc, file_size, &version)?;
+    writer.set_metadata(metadata)?;
+    for record in records {
+        writer.replace_record(record)?;
+    }
+    let (bufwriter, _) = writer.close()?;
+    sync_update(bufwriter)?;
 
     Ok(())
 }
 
+#[cfg(feature = "std")]
+// Flush the data of an update to disk, once the `ToC` is written.
+fn sync_update(bufwriter: BufWriter<std::fs::File>) -> Result<()> {
+    bufwriter
+        .into_inner()
+        .map_err(std::io::IntoInnerError::into_error)?
+        .sync_all()?;
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+// Read what is needed to update `outfile`: its `ToC`, its metadata, its size,
+// and its version.
+fn read_toc_for_update(
+    outfile: impl AsRef<Path>,
+) -> Result<(Vec<writers::TocEntry>, Metadata, u64, utils::Version)> {
+    let infile = OpenOptions::new().create(false).read(true).open(&outfile)?;
+    let file_size = infile.metadata()?.len();
+
+    let mut bufreader = BufReader::new(infile);
+    let version = readers::read_header(&mut bufreader)?;
+    // Only the latest version can be appended to, and it is the one which
+    // `append_mode` checks too: fail before reading the whole `ToC`.
+    if version != writers::Format::VERSION.into() {
+        return Err(EasypackError::InvalidFileError(format!(
+            "Cannot append to version {version:?}, expected {:?}: compact the file first",
+            writers::Format::VERSION
+        )));
+    }
+    let mut unpacker = readers::get_unpacker(&mut bufreader)?;
+    // Init the unpacker, otherwise the Toc is empty
+    // XXX Bad, should do something to avoid the need to "remember" about
+    // this detail :)
+    unpacker.init()?;
+
+    let mut old_toc = vec![];
+    unpacker.inspect_toc(&mut |entry| old_toc.push(entry.clone()))?;
+    let toc = old_toc
+        .into_iter()
+        .map(|entry| {
+            let checksum = entry.checksum.ok_or_else(|| {
+                EasypackError::InvalidFileError(format!(
+                    "Record {} has no checksum, while its version has one",
+                    entry.name
+                ))
+            })?;
+            Ok(
+                writers::TocEntry::new(entry.name, entry.data_start, entry.data_len, checksum)
+                    .with_compression(entry.compression, entry.uncompressed_len)
+                    .with_metadata(entry.metadata),
+            )
+        })
+        .collect::<Result<_>>()?;
+    Ok((toc, unpacker.toc().metadata().clone(), file_size, version))
+}
+
+#[cfg(feature = "std")]
 /// Pack the given `files` in the specified `outfile`.
+/// If `outfile` already exists, it is replaced as a whole, see
+/// `OpenMode::Truncate`.
+///
+/// The modification time and the permissions of every file are kept in the
+/// metadata of its record.
 ///
 /// # Errors
 ///
@@ -214,21 +462,305 @@ pub fn pack_files<P: AsRef<Path>, T: AsRef<str>>(
     outfile: P,
     pack_from: impl Iterator<Item = (T, P)>,
 ) -> Result<()> {
-    let outfile = OpenOptions::new().create(true).write(true).open(&outfile)?;
-    let bufwriter = BufWriter::new(outfile);
+    pack_files_with_mode(outfile, pack_from, OpenMode::default())
+}
+
+#[cfg(feature = "std")]
+/// Pack the given `files` in the specified `outfile`, which is opened as
+/// `mode` says. See `pack_files`.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors. With `OpenMode::CreateNew`,
+/// an `EasypackError::IoError` of kind `AlreadyExists` if `outfile` already
+/// exists.
+pub fn pack_files_with_mode<P: AsRef<Path>, T: AsRef<str>>(
+    outfile: P,
+    pack_from: impl Iterator<Item = (T, P)>,
+    mode: OpenMode,
+) -> Result<()> {
+    let outfile = AtomicFile::create(outfile, mode)?;
+    let bufwriter = BufWriter::new(outfile.file());
 
     let mut writer = Packer::from_writer(bufwriter).write_header()?;
     for (record_name, path) in pack_from {
-        let mut file = OpenOptions::new().read(true).open(&path)?;
-        let mut data = vec![];
-        let _howmany = file.read_to_end(&mut data)?;
-        let record = Record::new(record_name.as_ref().to_owned(), data);
-        writer.write_record(record)?;
+        pack_file(&mut writer, record_name.as_ref().to_owned(), path, true)?;
+    }
+    writer.close()?;
+    outfile.persist()
+}
+
+#[cfg(feature = "std")]
+/// What to do with the symlinks found by `pack_dir`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Leave them out of the packed file.
+    Skip,
+    /// Fail with `EasypackError::UnsupportedFile`.
+    Error,
+}
+
+#[cfg(feature = "std")]
+/// Pack all the files in the `root` directory, and its subdirectories, in the
+/// specified `outfile`.
+///
+/// The name of every record is the path of the file relative to `root`, with
+/// `/` as separator. The files are packed sorted by name, and their
+/// modification times are not kept (only their permissions are), so that the
+/// same tree always gives the same packed file. Symlinks are never followed:
+/// `symlinks` tells if they are skipped or if they are an error.
+/// Note that `outfile` should not be inside of `root`.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn pack_dir(
+    outfile: impl AsRef<Path>,
+    root: impl AsRef<Path>,
+    symlinks: Symlinks,
+) -> Result<()> {
+    pack_dir_with_mode(outfile, root, symlinks, OpenMode::default())
+}
+
+#[cfg(feature = "std")]
+/// Pack all the files in the `root` directory in the specified `outfile`,
+/// which is opened as `mode` says. See `pack_dir`.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors. With `OpenMode::CreateNew`,
+/// an `EasypackError::IoError` of kind `AlreadyExists` if `outfile` already
+/// exists.
+pub fn pack_dir_with_mode(
+    outfile: impl AsRef<Path>,
+    root: impl AsRef<Path>,
+    symlinks: Symlinks,
+    mode: OpenMode,
+) -> Result<()> {
+    let mut files = vec![];
+    collect_files(root.as_ref(), "", symlinks, &mut files)?;
+
+    let outfile = AtomicFile::create(outfile, mode)?;
+    let bufwriter = BufWriter::new(outfile.file());
+
+    let mut writer = Packer::from_writer(bufwriter).write_header()?;
+    for (record_name, path) in files {
+        pack_file(&mut writer, record_name, path, false)?;
+    }
+    writer.close()?;
+    outfile.persist()
+}
+
+#[cfg(feature = "std")]
+// Write the file at `path` as the record `name`, with the permissions of the
+// file as its metadata, and its modification time if `keep_mtime`.
+fn pack_file<W: Write>(
+    writer: &mut Packer<writers::RecordStep, W>,
+    name: String,
+    path: impl AsRef<Path>,
+    keep_mtime: bool,
+) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).open(&path)?;
+    let file_metadata = file.metadata()?;
+    let mut metadata = Metadata::new();
+    if keep_mtime {
+        // Files modified before the epoch are not worth the trouble.
+        if let Ok(mtime) = file_metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+        {
+            metadata.insert(Metadata::MTIME, MetadataValue::U64(mtime.as_secs()));
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.insert(
+            Metadata::MODE,
+            MetadataValue::U64((file_metadata.permissions().mode() & 0o7777).into()),
+        );
+    }
+
+    let mut record_writer = writer.begin_record(name)?;
+    std::io::copy(&mut file, &mut record_writer)?;
+    record_writer.set_metadata(metadata);
+    record_writer.finish_record()
+}
+
+#[cfg(feature = "std")]
+// Push the files in `dir` (and its subdirectories) in `files`, sorted by
+// name. Their record names are prefixed by `prefix`.
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    symlinks: Symlinks,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<()> {
+    let mut dir_entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    dir_entries.sort_by_key(std::fs::DirEntry::file_name);
+
+    for dir_entry in dir_entries {
+        let path = dir_entry.path();
+        let file_name = dir_entry.file_name();
+        let file_name = file_name.to_str().ok_or_else(|| {
+            EasypackError::UnsupportedFile(format!(
+                "The name of {} is not valid utf8",
+                path.display()
+            ))
+        })?;
+        let record_name = format!("{prefix}{file_name}");
+        // This does not follow symlinks.
+        let file_type = dir_entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&path, &format!("{record_name}/"), symlinks, files)?;
+        } else if file_type.is_file() {
+            files.push((record_name, path));
+        } else if !(file_type.is_symlink() && symlinks == Symlinks::Skip) {
+            return Err(EasypackError::UnsupportedFile(format!(
+                "{} is not a regular file",
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Copy only the live records of `infile` in a new `outfile`, leaving out the
+/// data and the `ToC`s which are not used anymore after updates.
+///
+/// `outfile` can be `infile` itself, since it is replaced only once the
+/// compacted file is complete.
+///
+/// `outfile` is always written with the latest version, whatever the version
+/// of `infile` is: this is how older files are upgraded, e.g. to update them.
+///
+/// # Returns
+///
+/// How many bytes were reclaimed.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn compact(infile: impl AsRef<Path>, outfile: impl AsRef<Path>) -> Result<u64> {
+    let in_size = std::fs::metadata(&infile)?.len();
+    let outfile = AtomicFile::create(outfile, OpenMode::Truncate)?;
+    compact_to(infile, outfile.file())?;
+    let out_size = outfile.file().metadata()?.len();
+    outfile.persist()?;
+    Ok(in_size.saturating_sub(out_size))
+}
+
+#[cfg(feature = "std")]
+/// Compact the packed `file` in place, see `compact`.
+/// As for every other write, the compacted file replaces `file` only once it
+/// is complete.
+///
+/// # Returns
+///
+/// How many bytes were reclaimed.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn compact_in_place(file: impl AsRef<Path>) -> Result<u64> {
+    compact(&file, &file)
+}
+
+#[cfg(feature = "std")]
+// Copy the live records of `infile` into `outfile`, as a new packed file.
+fn compact_to(infile: impl AsRef<Path>, outfile: &std::fs::File) -> Result<()> {
+    let mut archive = Archive::open(infile)?;
+    let entries: Vec<_> = archive
+        .entries()
+        .map(|entry| {
+            (
+                entry.name.clone(),
+                entry.data_len,
+                entry.compression,
+                entry.metadata.clone(),
+            )
+        })
+        .collect();
+
+    let bufwriter = BufWriter::new(outfile);
+    let mut writer = Packer::from_writer(bufwriter).write_header()?;
+    writer.set_metadata(archive.metadata().clone())?;
+    let mut written = std::collections::HashSet::new();
+    for (name, data_len, compression, metadata) in entries {
+        // If a name is there more than once, only the first one can be read.
+        if !written.insert(name.clone()) {
+            continue;
+        }
+        let not_found =
+            || EasypackError::InternalError(format!("Record {name} is in the ToC, but not found"));
+        if compression == Compression::None {
+            let mut record = archive.open_record(&name)?.ok_or_else(not_found)?;
+            let mut record_writer = writer.begin_record(name.clone())?;
+            // The reader fails on truncated data, or if the checksum does not
+            // match: never write a cut-down record with a new, valid checksum.
+            let copied = std::io::copy(&mut record, &mut record_writer)?;
+            if copied != data_len {
+                return Err(EasypackError::InvalidFileError(format!(
+                    "Record {name} is {data_len} bytes long, but only {copied} bytes can be read"
+                )));
+            }
+            record_writer.set_metadata(metadata);
+            record_writer.finish_record()?;
+        } else {
+            // Compressed records are read all in memory anyway.
+            let record = archive.get(&name)?.ok_or_else(not_found)?;
+            writer.write_compressed_record(record, compression)?;
+        }
     }
     writer.close()?;
     Ok(())
 }
 
+#[cfg(feature = "std")]
+/// List the records in `infile`, without reading any of their data.
+///
+/// # Returns
+///
+/// The entries of all the records, in the order they are stored, whatever the
+/// version of the file is.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn list_entries(infile: impl AsRef<Path>) -> Result<impl Iterator<Item = Entry>> {
+    let infile = OpenOptions::new().create(false).read(true).open(&infile)?;
+    let mut unpacker = readers::get_unpacker(BufReader::new(infile))?;
+    unpacker.init()?;
+
+    let mut entries = vec![];
+    unpacker.inspect_toc(&mut |entry| entries.push(entry.clone()))?;
+    Ok(entries.into_iter())
+}
+
+#[cfg(feature = "std")]
+/// Read the metadata of the archive in `infile`, without reading the records.
+///
+/// The whole `ToC` is read, since the metadata is at its start, and for the
+/// versions since 1.3 it is checked against the footer. Only the metadata is
+/// parsed out of it, not the entries.
+///
+/// # Returns
+///
+/// The metadata of the archive, which is empty for the versions before 1.6.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn read_archive_metadata(infile: impl AsRef<Path>) -> Result<Metadata> {
+    let infile = OpenOptions::new().create(false).read(true).open(&infile)?;
+    let mut bufreader = BufReader::new(infile);
+    let version = readers::read_header(&mut bufreader)?;
+    let (_, toc) = readers::parser::read_footer(&mut readers::IoSource(&mut bufreader), &version)?;
+    readers::parser::parse_archive_metadata(&toc, &version)
+}
+
+#[cfg(feature = "std")]
 /// Unpack a set of records associated with the `names` in the `infile`.
 ///
 /// # Returns
@@ -243,16 +775,12 @@ pub fn unpack_records<T: AsRef<str>>(
     infile: impl AsRef<Path>,
     names: impl Iterator<Item = T>,
 ) -> Result<(Vec<utils::Record>, Vec<String>)> {
-    let infile = OpenOptions::new().create(false).read(true).open(&infile)?;
-    let mut bufreader = BufReader::new(infile);
-
-    let mut unpacker = readers::get_unpacker(&mut bufreader)?;
-    unpacker.init()?;
+    let mut archive = Archive::open(infile)?;
     let mut found = vec![];
     let mut notfound = vec![];
     for name in names {
         let nameref = name.as_ref();
-        let record = unpacker.read_record(nameref)?;
+        let record = archive.get(nameref)?;
         record.map_or_else(
             || {
                 notfound.push(nameref.to_owned());
@@ -265,9 +793,46 @@ pub fn unpack_records<T: AsRef<str>>(
     Ok((found, notfound))
 }
 
+#[cfg(feature = "std")]
+/// Unpack a set of records associated with the `names` from the packed `data`,
+/// e.g. as returned by `pack_records_to_vec`. See `unpack_records`.
+///
+/// # Returns
+///
+/// A tuple with the records that were found, and the names of these that we
+/// did not find.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors.
+pub fn unpack_records_from_slice<T: AsRef<str>>(
+    data: &[u8],
+    names: impl Iterator<Item = T>,
+) -> Result<(Vec<utils::Record>, Vec<String>)> {
+    let archive = Archive::from_bytes(data)?;
+    let mut found = vec![];
+    let mut notfound = vec![];
+    for name in names {
+        let nameref = name.as_ref();
+        match (archive.entry(nameref), archive.get(nameref)?) {
+            (Some(entry), Some(record_data)) => found.push(
+                utils::Record::new(nameref.to_owned(), record_data.into_owned())
+                    .with_metadata(entry.metadata.clone()),
+            ),
+            _ => notfound.push(nameref.to_owned()),
+        }
+    }
+    Ok((found, notfound))
+}
+
+#[cfg(feature = "std")]
 /// Unpack data from `infile`.
 /// The user has to provide a slice of tuples(record name, output file).
 ///
+/// The modification time and the permissions in the metadata of the records,
+/// if any, are restored on the output files. The setuid, setgid and sticky
+/// bits are never restored.
+///
 /// # Returns
 ///
 /// The list of records that were not found in the file.
@@ -279,16 +844,17 @@ pub fn unpack_files<T: AsRef<str>, P: AsRef<Path>>(
     infile: P,
     unpack_to: impl Iterator<Item = (T, P)>,
 ) -> Result<Vec<String>> {
-    let infile = OpenOptions::new().create(false).read(true).open(&infile)?;
-    let mut bufreader = BufReader::new(infile);
-
-    let mut unpacker = readers::get_unpacker(&mut bufreader)?;
-    unpacker.init()?;
+    let mut archive = Archive::open(infile)?;
     let mut res = vec![];
     for (record_name, outpath) in unpack_to {
-        if let Some(record) = unpacker.read_record(record_name.as_ref())? {
-            let mut outfile = OpenOptions::new().create(true).write(true).open(outpath)?;
+        if let Some(record) = archive.get(record_name.as_ref())? {
+            let mut outfile = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(outpath)?;
             outfile.write_all(&record.data)?;
+            restore_metadata(&outfile, &record.metadata)?;
         } else {
             res.push(record_name.as_ref().to_owned());
         }
@@ -296,7 +862,96 @@ pub fn unpack_files<T: AsRef<str>, P: AsRef<Path>>(
     Ok(res)
 }
 
-#[cfg(test)]
+#[cfg(feature = "std")]
+/// Unpack all the records in `infile` into the `out_dir` directory.
+///
+/// Every record is written to `out_dir/name`: the names are used as relative
+/// paths, with `/` as separator, and the intermediate directories are created
+/// as needed. The metadata of the records is restored as `unpack_files` does.
+///
+/// # Errors
+///
+/// Check `EasyPackError` for the possible errors. If any name is an absolute
+/// path, or has `..` in it, nothing is written and
+/// `EasypackError::UnsafeRecordName` is returned.
+pub fn unpack_all(infile: impl AsRef<Path>, out_dir: impl AsRef<Path>) -> Result<()> {
+    let mut archive = Archive::open(infile)?;
+    // Check all the names first, so that nothing is written from a file which
+    // is not safe to unpack.
+    let unpack_to = archive
+        .names()
+        .map(|name| Ok((name.to_owned(), record_path(out_dir.as_ref(), name)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (name, outpath) in unpack_to {
+        if let Some(parent) = outpath.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let record = archive.get(&name)?.ok_or_else(|| {
+            EasypackError::InternalError(format!("Record {name} is in the ToC, but not found"))
+        })?;
+        let mut outfile = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(outpath)?;
+        outfile.write_all(&record.data)?;
+        restore_metadata(&outfile, &record.metadata)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+// Set the modification time and the permissions of `file` from `metadata`,
+// once all the data is written. The archive may come from anywhere: the
+// setuid, setgid and sticky bits are never set.
+fn restore_metadata(file: &std::fs::File, metadata: &Metadata) -> Result<()> {
+    if let Some(mtime) = metadata
+        .mtime()
+        .and_then(|mtime| std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(mtime)))
+    {
+        file.set_modified(mtime)?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = metadata.mode() {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+// Where to unpack the record `name` in `out_dir`, making sure that it is
+// inside of it.
+fn record_path(out_dir: &Path, name: &str) -> Result<PathBuf> {
+    let mut path = out_dir.to_path_buf();
+    let mut is_empty = true;
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => {
+                path.push(part);
+                is_empty = false;
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(EasypackError::UnsafeRecordName(format!(
+                    "Record {name} would be unpacked outside of {}",
+                    out_dir.display()
+                )));
+            }
+        }
+    }
+    if is_empty {
+        return Err(EasypackError::UnsafeRecordName(format!(
+            "Record {name:?} has no file name"
+        )));
+    }
+    Ok(path)
+}
+
+#[cfg(all(test, feature = "std"))]
+// The results are checked by index, the first one as the others.
+#[allow(clippy::get_first)]
 pub mod test {
     use super::*;
     use crate::readers::VersionedUnpacker;
@@ -304,7 +959,7 @@ pub mod test {
     use crate::{readers, writers};
 
     use predicates::prelude::*;
-    use std::io::{BufReader, BufWriter, Cursor};
+    use std::io::{BufReader, BufWriter, Cursor, Read};
     use std::path::PathBuf;
     use std::str::FromStr;
 
@@ -329,10 +984,12 @@ pub mod test {
     #[test]
     /// Test that we can write a `ver_1_1` header, and read it.
     fn write_read_header_1_1() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::writers::ver_1_1::write_header;
+
         let mut buff = Cursor::new(vec![]);
         {
             let mut w = BufWriter::new(&mut buff);
-            writers::write_header(&mut w)?;
+            write_header(&mut w)?;
         }
         {
             let mut r = BufReader::new(&mut buff);
@@ -342,10 +999,32 @@ pub mod test {
         Ok(())
     }
 
+    #[test]
+    /// Test that we can write the header of every later version, read it, and
+    /// get the unpacker for it.
+    fn write_read_header_versions() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        type WriteHeader = fn(&mut Cursor<Vec<u8>>) -> Result<()>;
+        let versions: [(WriteHeader, (u8, u8)); 6] = [
+            (writers::ver_1_2::write_header, (1, 2)),
+            (writers::ver_1_3::write_header, (1, 3)),
+            (writers::ver_1_4::write_header, (1, 4)),
+            (writers::ver_1_5::write_header, (1, 5)),
+            (writers::ver_1_6::write_header, (1, 6)),
+            (writers::write_header, (1, 7)),
+        ];
+        for (write_header, version) in versions {
+            let mut buff = Cursor::new(vec![]);
+            write_header(&mut buff)?;
+            assert_eq!(readers::read_header(&mut buff)?, version.into());
+            assert!(readers::get_unpacker(&mut buff).is_ok());
+        }
+        Ok(())
+    }
+
     #[test]
     /// Mixing versions should not work.
     fn read_mix_version() -> std::result::Result<(), Box<dyn std::error::Error>> {
-        use crate::readers::ver_1_0::Unpacker;
+        use crate::readers::Unpacker;
         use crate::writers::ver_1_1::Packer;
         let mut buff = Cursor::new(vec![]);
 
@@ -362,7 +1041,7 @@ pub mod test {
         writer.close()?;
 
         let mut buffreader = BufReader::new(&mut buff);
-        let mut reader = Unpacker::from_reader(&mut buffreader);
+        let mut reader = Unpacker::from_reader(&mut buffreader, (1, 0).into());
         reader.init()?;
         let r = reader.read_record("asd")?;
         assert!(r.is_none());
@@ -376,7 +1055,7 @@ pub mod test {
     #[test]
     /// Mixing versions should not work, attempt number 2.
     fn read_mix_version_2() -> std::result::Result<(), Box<dyn std::error::Error>> {
-        use crate::readers::ver_1_1::Unpacker;
+        use crate::readers::Unpacker;
         use crate::writers::ver_1_0::Packer;
         let mut buff = Cursor::new(vec![]);
 
@@ -393,7 +1072,7 @@ pub mod test {
         writer.close()?;
 
         let mut buffreader = BufReader::new(&mut buff);
-        let mut reader = Unpacker::from_reader(&mut buffreader);
+        let mut reader = Unpacker::from_reader(&mut buffreader, (1, 1).into());
         // The reader needs more data to read, using a different reader does not work!
         assert!(reader.init().is_err());
 
@@ -403,7 +1082,7 @@ pub mod test {
     #[test]
     /// We can write and read records.
     fn read_write_records_1_0() -> std::result::Result<(), Box<dyn std::error::Error>> {
-        use crate::readers::ver_1_0::Unpacker;
+        use crate::readers::Unpacker;
         use crate::writers::ver_1_0::Packer;
         let mut buff = Cursor::new(vec![]);
 
@@ -420,7 +1099,7 @@ pub mod test {
         writer.close()?;
 
         let mut buffreader = BufReader::new(&mut buff);
-        let mut reader = Unpacker::from_reader(&mut buffreader);
+        let mut reader = Unpacker::from_reader(&mut buffreader, (1, 0).into());
         reader.init()?;
         let r = reader.read_record("asd")?;
         assert!(r.is_none());
@@ -435,7 +1114,7 @@ pub mod test {
     #[test]
     /// We can write and read records.
     fn read_write_records_1_1() -> std::result::Result<(), Box<dyn std::error::Error>> {
-        use crate::readers::ver_1_1::Unpacker;
+        use crate::readers::Unpacker;
         use crate::writers::ver_1_1::Packer;
 
         let mut buff = Cursor::new(vec![]);
@@ -453,7 +1132,7 @@ pub mod test {
         writer.close()?;
 
         let mut buffreader = BufReader::new(&mut buff);
-        let mut reader = Unpacker::from_reader(&mut buffreader);
+        let mut reader = Unpacker::from_reader(&mut buffreader, (1, 1).into());
         reader.init()?;
         let r = reader.read_record("asd")?;
         assert!(r.is_none());
@@ -467,17 +1146,196 @@ pub mod test {
     }
 
     #[test]
-    /// Complete test using files.
-    fn pack_unpack_files() -> std::result::Result<(), Box<dyn std::error::Error>> {
-        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/packedfile.bin")?);
-        let infile1 = Tempfile::from_path(PathBuf::from_str("/tmp/afile.txt")?);
-        let infile2 = Tempfile::from_path(PathBuf::from_str("/tmp/another.txt")?);
+    /// We can write and read records.
+    fn read_write_records_1_2() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::readers::Unpacker;
+        use crate::writers::ver_1_2::Packer;
+
+        let mut buff = Cursor::new(vec![]);
+
+        let buffwriter = BufWriter::new(&mut buff);
+        let mut writer = Packer::from_writer(buffwriter).write_header()?;
+        writer.write_record(utils::Record::new(
+            "file_1".to_owned(),
+            vec![0x12, 0x34, 0x56],
+        ))?;
+        writer.write_record(utils::Record::new("empty".to_owned(), vec![]))?;
+        writer.close()?;
+
+        let mut buffreader = BufReader::new(&mut buff);
+        let mut reader = Unpacker::from_reader(&mut buffreader, (1, 2).into());
+        reader.init()?;
+        let r = reader.read_record("asd")?;
+        assert!(r.is_none());
+        let r = reader.read_record("file_1")?;
+        assert_eq!(r.unwrap().data, vec![0x12, 0x34, 0x56]);
+        let r = reader.read_record("empty")?;
+        assert!(r.unwrap().data.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that we can write some records, and read them back.
+    fn read_write_records_1_3() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::readers::Unpacker;
+        use crate::writers::ver_1_3::Packer;
+
+        let mut buff = Cursor::new(vec![]);
+
+        let buffwriter = BufWriter::new(&mut buff);
+        let mut writer = Packer::from_writer(buffwriter).write_header()?;
+        writer.write_record(utils::Record::new(
+            "file_1".to_owned(),
+            vec![0x12, 0x34, 0x56],
+        ))?;
+        writer.write_record(utils::Record::new("empty".to_owned(), vec![]))?;
+        writer.close()?;
+
+        let mut buffreader = BufReader::new(&mut buff);
+        let mut reader = Unpacker::from_reader(&mut buffreader, (1, 3).into());
+        reader.init()?;
+        let r = reader.read_record("asd")?;
+        assert!(r.is_none());
+        let r = reader.read_record("file_1")?;
+        assert_eq!(r.unwrap().data, vec![0x12, 0x34, 0x56]);
+        let r = reader.read_record("empty")?;
+        assert!(r.unwrap().data.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    /// If an update did not complete, the file is read as it was before it,
+    /// and it can be updated again.
+    fn recover_incomplete_update() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/incompleteupdate.bin")?);
+        pack_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+        )?;
+        pack_records_update(
+            &*packed_file,
+            [utils::Record::new("c2".into(), vec![0x56])].into_iter(),
+        )?;
+        let valid = std::fs::read(&*packed_file)?;
+        pack_records_update(
+            &*packed_file,
+            [utils::Record::new("c3".into(), vec![0x78; 100])].into_iter(),
+        )?;
+        let updated = std::fs::read(&*packed_file)?;
+
+        // Stop the last update anywhere, or leave garbage after it.
+        let mut garbage = valid.clone();
+        garbage.extend_from_slice(b"SMPF garbage SMPF");
+        let mut tails = vec![garbage];
+        for len in (valid.len() + 1)..updated.len() {
+            tails.push(updated[..len].to_vec());
+        }
+        for tail in tails {
+            std::fs::write(&*packed_file, &tail)?;
+            let archive = Archive::open(&*packed_file)?;
+            assert_eq!(archive.names().collect::<Vec<_>>(), ["c1", "c2"]);
+            assert_eq!(
+                verify(&*packed_file)?,
+                [Problem::IncompleteUpdate {
+                    valid_len: valid.len() as u64,
+                    file_len: tail.len() as u64
+                }]
+            );
+        }
+
+        pack_records_update(
+            &*packed_file,
+            [utils::Record::new("c3".into(), vec![0x9A])].into_iter(),
+        )?;
+        let res = unpack_records(&*packed_file, ["c1", "c2", "c3"].into_iter())?;
+        let data: Vec<_> = res.0.into_iter().map(|record| record.data).collect();
+        assert_eq!(data, [vec![0x12, 0x34], vec![0x56], vec![0x9A]]);
+        assert_eq!(verify(&*packed_file)?, []);
+
+        // Going back as far as needed.
+        let mut broken = valid;
+        let len = broken.len();
+        broken[len - 1] ^= 0x01;
+        std::fs::write(&*packed_file, &broken)?;
+        let archive = Archive::open(&*packed_file)?;
+        assert_eq!(archive.names().collect::<Vec<_>>(), ["c1"]);
+
+        // Without any valid footer, there is nothing to read.
+        pack_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+        )?;
+        let mut broken = std::fs::read(&*packed_file)?;
+        let len = broken.len();
+        broken[len - 1] ^= 0x01;
+        std::fs::write(&*packed_file, &broken)?;
+        assert!(Archive::open(&*packed_file).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// A corrupted record is detected, both reading it all and streaming it.
+    fn read_corrupted_record_1_2() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::readers::Unpacker;
+        use crate::writers::ver_1_2::Packer;
+
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(utils::Record::new("good".to_owned(), vec![0x12, 0x34]))?;
+            writer.write_record(utils::Record::new("bad".to_owned(), vec![0x56, 0x78]))?;
+            writer.close()?;
+        }
+        // Flip a bit in the data of "bad", right after the header and "good".
+        let pos = usize::try_from(utils::HEADER_SIZE)? + 2;
+        buff.get_mut()[pos] ^= 0x01;
+
+        let mut reader = Unpacker::from_reader(&mut buff, (1, 2).into());
+        reader.init()?;
+        assert_eq!(reader.read_record("good")?.unwrap().data, vec![0x12, 0x34]);
+        assert!(matches!(
+            reader.read_record("bad"),
+            Err(EasypackError::ChecksumMismatch(_))
+        ));
+
+        let mut data = vec![];
+        let err = reader
+            .open_record("bad")?
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref()
+                .and_then(|e| e.downcast_ref::<EasypackError>()),
+            Some(EasypackError::ChecksumMismatch(_))
+        ));
+
+        // Partial reads can't be verified, and they are fine.
+        let mut record_reader = reader.open_record("bad")?.unwrap();
+        let mut buf = [0; 1];
+        record_reader.read_exact(&mut buf)?;
+        assert_eq!(buf, [0x57]);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Complete test using files.
+    fn pack_unpack_files() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/packedfile.bin")?);
+        let infile1 = Tempfile::from_path(PathBuf::from_str("/tmp/afile.txt")?);
+        let infile2 = Tempfile::from_path(PathBuf::from_str("/tmp/another.txt")?);
 
         // Prepare the 2 input files.
         {
             let mut f = OpenOptions::new()
                 .create(true)
                 .write(true)
+                .truncate(true)
                 .open(&*infile1)?;
             f.write_all(b"This is some content!")?;
             f.flush()?;
@@ -486,6 +1344,7 @@ pub mod test {
             let mut f = OpenOptions::new()
                 .create(true)
                 .write(true)
+                .truncate(true)
                 .open(&*infile2)?;
             f.write_all(b"something else")?;
             f.flush()?;
@@ -514,6 +1373,81 @@ pub mod test {
         Ok(())
     }
 
+    #[test]
+    /// The modification time and the permissions of the files are kept.
+    fn pack_unpack_files_metadata() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/packedmetadata.bin")?);
+        let infile = Tempfile::from_path(PathBuf::from_str("/tmp/metadatafile.txt")?);
+        let outfile = Tempfile::from_path(PathBuf::from_str("/tmp/metadatafile_out.txt")?);
+        let out_dir = PathBuf::from_str("/tmp/metadata_dir")?;
+        let _ = std::fs::remove_dir_all(&out_dir);
+
+        // Only whole seconds are kept.
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_234_567_890);
+        {
+            let f = std::fs::File::create(&*infile)?;
+            (&f).write_all(b"Some content")?;
+            f.set_modified(mtime)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                f.set_permissions(std::fs::Permissions::from_mode(0o640))?;
+            }
+        }
+
+        pack_files(&*packed_file, [("c1", &*infile)].into_iter())?;
+        let entry = list_entries(&*packed_file)?.next().ok_or("no entry")?;
+        assert_eq!(entry.metadata.mtime(), Some(1_234_567_890));
+        #[cfg(unix)]
+        assert_eq!(entry.metadata.mode(), Some(0o640));
+
+        unpack_files(&*packed_file, [("c1", &*outfile)].into_iter())?;
+        unpack_all(&*packed_file, &out_dir)?;
+        for path in [outfile.to_path_buf(), out_dir.join("c1")] {
+            let metadata = std::fs::metadata(path)?;
+            assert_eq!(metadata.modified()?, mtime);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(metadata.permissions().mode() & 0o7777, 0o640);
+            }
+        }
+
+        std::fs::remove_dir_all(&out_dir)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    /// Only the permission bits of the mode are restored, never the setuid,
+    /// setgid and sticky bits.
+    fn unpack_files_special_mode() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/packedsetuid.bin")?);
+        let outfile = Tempfile::from_path(PathBuf::from_str("/tmp/setuid_out.txt")?);
+        let out_dir = PathBuf::from_str("/tmp/setuid_dir")?;
+        let _ = std::fs::remove_dir_all(&out_dir);
+        pack_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12])
+                .with_metadata(Metadata::new().with_mode(0o4755))]
+            .into_iter(),
+        )?;
+
+        unpack_files(&*packed_file, [("c1", &*outfile)].into_iter())?;
+        unpack_all(&*packed_file, &out_dir)?;
+        for path in [outfile.to_path_buf(), out_dir.join("c1")] {
+            let mode = std::fs::metadata(path)?.permissions().mode();
+            assert_eq!(mode & 0o7777, 0o755);
+        }
+
+        std::fs::remove_dir_all(&out_dir)?;
+        Ok(())
+    }
+
     #[test]
     /// Complete test using files.
     fn pack_unpack_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
@@ -545,6 +1479,684 @@ pub mod test {
         Ok(())
     }
 
+    #[test]
+    /// Records can be packed and unpacked in memory, the data being the same
+    /// as in a file.
+    fn pack_unpack_records_in_memory() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/inmemorypacked.bin")?);
+        let records = || {
+            [
+                utils::Record::new("c1".into(), vec![0x12, 0x34])
+                    .with_metadata(Metadata::new().with_mtime(1_234_567_890)),
+                utils::Record::new("c2".into(), vec![0x34]),
+            ]
+            .into_iter()
+        };
+        let data = pack_records_to_vec(records())?;
+        pack_records(&*packed_file, records())?;
+        assert_eq!(std::fs::read(&*packed_file)?, data);
+
+        let (found, notfound) = unpack_records_from_slice(&data, ["c2", "c1", "nope"].into_iter())?;
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].name, "c2");
+        assert_eq!(found[0].data, vec![0x34]);
+        assert!(found[0].metadata.is_empty());
+        assert_eq!(found[1].name, "c1");
+        assert_eq!(found[1].data, vec![0x12, 0x34]);
+        assert_eq!(found[1].metadata.mtime(), Some(1_234_567_890));
+        assert_eq!(notfound, ["nope"]);
+
+        assert!(pack_records_to_vec(std::iter::empty())?.starts_with(utils::FILE_TYPE.as_bytes()));
+        assert!(unpack_records_from_slice(b"This is just text.", ["c1"].into_iter()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// List the records of a file, without knowing its version.
+    fn list_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/listpacked.bin")?);
+        pack_records(
+            &*packed_file,
+            [
+                utils::Record::new("c1".into(), vec![0x12, 0x34]),
+                utils::Record::new("c2".into(), vec![]),
+                utils::Record::new("c3".into(), vec![0x34]),
+            ]
+            .into_iter(),
+        )?;
+
+        let entries: Vec<_> = list_entries(&*packed_file)?
+            .map(|entry| (entry.name, entry.data_start, entry.data_len))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("c1".to_owned(), 6, 2),
+                ("c2".to_owned(), 8, 0),
+                ("c3".to_owned(), 8, 1)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Unpack all the records in a directory.
+    fn unpack_all_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/unpackallpacked.bin")?);
+        let out_dir = PathBuf::from_str("/tmp/unpackall_dir")?;
+        let _ = std::fs::remove_dir_all(&out_dir);
+        pack_records(
+            &*packed_file,
+            [
+                utils::Record::new("c1".into(), vec![0x12, 0x34]),
+                utils::Record::new("dir/sub/c2".into(), vec![0x34]),
+                utils::Record::new("./dir/c3".into(), vec![]),
+            ]
+            .into_iter(),
+        )?;
+
+        unpack_all(&*packed_file, &out_dir)?;
+        assert_eq!(std::fs::read(out_dir.join("c1"))?, [0x12, 0x34]);
+        assert_eq!(std::fs::read(out_dir.join("dir/sub/c2"))?, [0x34]);
+        assert_eq!(std::fs::read(out_dir.join("dir/c3"))?, []);
+
+        std::fs::remove_dir_all(&out_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Names that would write outside of the directory are refused, and
+    /// nothing is written.
+    fn unpack_all_unsafe_names() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let out_dir = PathBuf::from_str("/tmp/unpackall_unsafe_dir")?;
+        let _ = std::fs::remove_dir_all(&out_dir);
+
+        for name in ["../c2", "dir/../../c2", "/tmp/c2", "", "."] {
+            let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/unpackallunsafe.bin")?);
+            pack_records(
+                &*packed_file,
+                [
+                    utils::Record::new("c1".into(), vec![0x12, 0x34]),
+                    utils::Record::new(name.into(), vec![0x34]),
+                ]
+                .into_iter(),
+            )?;
+            let res = unpack_all(&*packed_file, &out_dir);
+            assert!(
+                matches!(res, Err(EasypackError::UnsafeRecordName(_))),
+                "{name}: {res:?}"
+            );
+            assert!(!out_dir.exists());
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Pack a whole directory, and get it back.
+    fn pack_dir_unpack_all() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/packdir.bin")?);
+        let packed_file_2 = Tempfile::from_path(PathBuf::from_str("/tmp/packdir_2.bin")?);
+        let in_dir = PathBuf::from_str("/tmp/packdir_in")?;
+        let in_dir_2 = PathBuf::from_str("/tmp/packdir_in_2")?;
+        let out_dir = PathBuf::from_str("/tmp/packdir_out")?;
+        for dir in [&in_dir, &in_dir_2, &out_dir] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+
+        // The same tree, created in a different order, at different times.
+        let files = [
+            ("b", vec![0x12, 0x34]),
+            ("a/z", vec![0x56]),
+            ("a/c/d", vec![]),
+            ("a/b", vec![0x78]),
+        ];
+        let write_file = |path: PathBuf, data: &[u8], mtime: u64| -> std::io::Result<()> {
+            std::fs::create_dir_all(path.parent().unwrap_or(&path))?;
+            std::fs::write(&path, data)?;
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(UNIX_EPOCH + Duration::from_secs(mtime))
+        };
+        for (name, data) in &files {
+            write_file(in_dir.join(name), data, 1_000_000_000)?;
+        }
+        for (name, data) in files.iter().rev() {
+            write_file(in_dir_2.join(name), data, 1_234_567_890)?;
+        }
+        pack_dir(&*packed_file, &in_dir, Symlinks::Error)?;
+        pack_dir(&*packed_file_2, &in_dir_2, Symlinks::Error)?;
+        assert_eq!(
+            std::fs::read(&*packed_file)?,
+            std::fs::read(&*packed_file_2)?
+        );
+
+        let entries: Vec<_> = list_entries(&*packed_file)?.collect();
+        let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["a/b", "a/c/d", "a/z", "b"]);
+        assert!(entries.iter().all(|entry| entry.metadata.mtime().is_none()));
+
+        unpack_all(&*packed_file, &out_dir)?;
+        for (name, data) in &files {
+            assert_eq!(&std::fs::read(out_dir.join(name))?, data);
+        }
+
+        for dir in [&in_dir, &in_dir_2, &out_dir] {
+            std::fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    /// Symlinks are skipped, or refused.
+    fn pack_dir_symlinks() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/packdir_symlinks.bin")?);
+        let in_dir = PathBuf::from_str("/tmp/packdir_symlinks_in")?;
+        let _ = std::fs::remove_dir_all(&in_dir);
+        std::fs::create_dir_all(in_dir.join("dir"))?;
+        std::fs::write(in_dir.join("dir/file"), [0x12])?;
+        std::os::unix::fs::symlink("file", in_dir.join("dir/link"))?;
+        std::os::unix::fs::symlink("dir", in_dir.join("dirlink"))?;
+
+        pack_dir(&*packed_file, &in_dir, Symlinks::Skip)?;
+        let names: Vec<_> = list_entries(&*packed_file)?
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["dir/file"]);
+
+        assert!(matches!(
+            pack_dir(&*packed_file, &in_dir, Symlinks::Error),
+            Err(EasypackError::UnsupportedFile(_))
+        ));
+
+        std::fs::remove_dir_all(&in_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Remove and replace records in a file, without making a new one.
+    fn remove_replace_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/removereplace.bin")?);
+        pack_records(
+            &*packed_file,
+            [
+                utils::Record::new("c1".into(), vec![0x12, 0x34]),
+                utils::Record::new("c2".into(), vec![0x34]),
+                utils::Record::new("c3".into(), vec![0x56]),
+            ]
+            .into_iter(),
+        )?;
+
+        let notfound = remove_records(&*packed_file, ["c2", "nope"].into_iter())?;
+        assert_eq!(notfound, ["nope"]);
+        let names: Vec<_> = list_entries(&*packed_file)?
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["c1", "c3"]);
+
+        // Nothing to remove, the file is left as it is.
+        let file_size = std::fs::metadata(&*packed_file)?.len();
+        let notfound = remove_records(&*packed_file, ["c2"].into_iter())?;
+        assert_eq!(notfound, ["c2"]);
+        assert_eq!(std::fs::metadata(&*packed_file)?.len(), file_size);
+
+        replace_records(
+            &*packed_file,
+            [
+                utils::Record::new("c1".into(), vec![0x78]),
+                utils::Record::new("c2".into(), vec![0x9A]),
+            ]
+            .into_iter(),
+        )?;
+        let entries: Vec<_> = list_entries(&*packed_file)?
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(entries, ["c1", "c3", "c2"]);
+        let res = unpack_records(&*packed_file, ["c1", "c2", "c3"].into_iter())?;
+        let data: Vec<_> = res.0.into_iter().map(|record| record.data).collect();
+        assert_eq!(data, [vec![0x78], vec![0x9A], vec![0x56]]);
+
+        assert_eq!(verify(&*packed_file)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    /// Compact a file after some updates.
+    fn compact_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/compact.bin")?);
+        let compacted_file = Tempfile::from_path(PathBuf::from_str("/tmp/compacted.bin")?);
+        let fresh_file = Tempfile::from_path(PathBuf::from_str("/tmp/compact_fresh.bin")?);
+        pack_records(
+            &*packed_file,
+            [
+                utils::Record::new("c1".into(), vec![0x12, 0x34]),
+                utils::Record::new("c2".into(), vec![0x34]),
+            ]
+            .into_iter(),
+        )?;
+        pack_records_update(
+            &*packed_file,
+            [utils::Record::new("c3".into(), vec![0x56])].into_iter(),
+        )?;
+        replace_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x78])].into_iter(),
+        )?;
+        remove_records(&*packed_file, ["c2"].into_iter())?;
+
+        // The same content, packed at once.
+        pack_records(
+            &*fresh_file,
+            [
+                utils::Record::new("c1".into(), vec![0x78]),
+                utils::Record::new("c3".into(), vec![0x56]),
+            ]
+            .into_iter(),
+        )?;
+        let fresh = std::fs::read(&*fresh_file)?;
+
+        let packed_size = std::fs::metadata(&*packed_file)?.len();
+        let reclaimed = compact(&*packed_file, &*compacted_file)?;
+        assert_eq!(reclaimed, packed_size - fresh.len() as u64);
+        assert_eq!(std::fs::read(&*compacted_file)?, fresh);
+
+        let reclaimed = compact_in_place(&*packed_file)?;
+        assert_eq!(reclaimed, packed_size - fresh.len() as u64);
+        assert_eq!(std::fs::read(&*packed_file)?, fresh);
+
+        // Nothing more to reclaim.
+        assert_eq!(compact_in_place(&*packed_file)?, 0);
+        assert_eq!(std::fs::read(&*packed_file)?, fresh);
+        Ok(())
+    }
+
+    #[test]
+    /// Compressed records stay compressed through updates and compaction.
+    fn update_compact_compressed_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/compact_compressed.bin")?);
+        let text = b"Some text, which is repeated. ".repeat(100);
+        {
+            let file = std::fs::File::create(&*packed_file)?;
+            let mut writer = Packer::from_writer(BufWriter::new(file)).write_header()?;
+            writer.write_compressed_record(
+                utils::Record::new("c1".into(), text.clone()),
+                Compression::Lz,
+            )?;
+            writer.write_record(utils::Record::new("c2".into(), vec![0x12]))?;
+            writer.close()?;
+        }
+        pack_records_update(
+            &*packed_file,
+            [utils::Record::new("c3".into(), vec![0x34])].into_iter(),
+        )?;
+        remove_records(&*packed_file, ["c2"].into_iter())?;
+        assert!(compact_in_place(&*packed_file)? > 0);
+
+        let entries: Vec<_> = list_entries(&*packed_file)?
+            .map(|entry| (entry.name, entry.compression))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("c1".to_owned(), Compression::Lz),
+                ("c3".to_owned(), Compression::None)
+            ]
+        );
+        let res = unpack_records(&*packed_file, ["c1", "c3"].into_iter())?;
+        let data: Vec<_> = res.0.into_iter().map(|record| record.data).collect();
+        assert_eq!(data, [text, vec![0x34]]);
+        assert_eq!(verify(&*packed_file)?, []);
+        Ok(())
+    }
+
+    #[test]
+    /// The metadata of the records is kept through updates and compaction.
+    fn update_compact_metadata() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/compact_metadata.bin")?);
+        let metadata = Metadata::new()
+            .with_mtime(42)
+            .with_content_type("text/plain");
+        pack_records(
+            &*packed_file,
+            [
+                utils::Record::new("c1".into(), b"Some text".to_vec())
+                    .with_metadata(metadata.clone()),
+                utils::Record::new("c2".into(), vec![0x12]),
+            ]
+            .into_iter(),
+        )?;
+        pack_records_update(
+            &*packed_file,
+            [utils::Record::new("c3".into(), vec![0x34]).with_metadata(metadata.clone())]
+                .into_iter(),
+        )?;
+        remove_records(&*packed_file, ["c2"].into_iter())?;
+        assert!(compact_in_place(&*packed_file)? > 0);
+
+        let res = unpack_records(&*packed_file, ["c1", "c3"].into_iter())?;
+        for record in res.0 {
+            assert_eq!(record.metadata, metadata);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// The metadata of the archive is kept through updates and compaction,
+    /// and can be read on its own, even after an interrupted update.
+    fn update_compact_archive_metadata() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file =
+            Tempfile::from_path(PathBuf::from_str("/tmp/compact_archive_metadata.bin")?);
+        let metadata = Metadata::new()
+            .with_value("build-id", MetadataValue::Text("1234abcd".to_owned()))
+            .with_value("description", MetadataValue::Text("Some assets".to_owned()));
+        {
+            let file = std::fs::File::create(&*packed_file)?;
+            let mut writer = Packer::from_writer(BufWriter::new(file)).write_header()?;
+            writer.write_record(utils::Record::new("c1".into(), vec![0x12]))?;
+            writer.set_metadata(metadata.clone())?;
+            writer.write_record(utils::Record::new("c2".into(), vec![0x34]))?;
+            writer.close()?;
+        }
+        assert_eq!(read_archive_metadata(&*packed_file)?, metadata);
+
+        pack_records_update(
+            &*packed_file,
+            [utils::Record::new("c3".into(), vec![0x56])].into_iter(),
+        )?;
+        replace_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x78])].into_iter(),
+        )?;
+        remove_records(&*packed_file, ["c2"].into_iter())?;
+        assert_eq!(read_archive_metadata(&*packed_file)?, metadata);
+        assert!(compact_in_place(&*packed_file)? > 0);
+        assert_eq!(read_archive_metadata(&*packed_file)?, metadata);
+        assert_eq!(Archive::open(&*packed_file)?.metadata(), &metadata);
+        assert_eq!(verify(&*packed_file)?, []);
+
+        // The `ToC` is checked: after an interrupted update, the metadata is
+        // read from the last valid one.
+        OpenOptions::new()
+            .append(true)
+            .open(&*packed_file)?
+            .write_all(&[0x9a; 100])?;
+        assert_eq!(read_archive_metadata(&*packed_file)?, metadata);
+        Ok(())
+    }
+
+    #[test]
+    /// Files written before version 1.6 have no metadata.
+    fn read_archive_metadata_old_version() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/old_archive_metadata.bin")?);
+        {
+            let file = std::fs::File::create(&*packed_file)?;
+            let mut writer =
+                writers::ver_1_5::Packer::from_writer(BufWriter::new(file)).write_header()?;
+            writer.write_record(utils::Record::new("c1".into(), vec![0x12]))?;
+            writer.close()?;
+        }
+        assert!(read_archive_metadata(&*packed_file)?.is_empty());
+        assert!(Archive::open(&*packed_file)?.metadata().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// If the compaction fails, the file is left as it is.
+    fn compact_in_place_invalid() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/compact_invalid.bin")?);
+        std::fs::write(&*packed_file, b"This is just text.")?;
+        assert!(compact_in_place(&*packed_file).is_err());
+        assert_eq!(std::fs::read(&*packed_file)?, b"This is just text.");
+        assert!(
+            !std::fs::read_dir("/tmp")?.any(|entry| entry.is_ok_and(|entry| entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".compact_invalid.bin.")))
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Compacting a corrupt file fails, rather than writing a valid file with
+    /// the corrupt data.
+    fn compact_corrupt() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/compact_corrupt.bin")?);
+        let compacted_file =
+            Tempfile::from_path(PathBuf::from_str("/tmp/compact_corrupt_out.bin")?);
+
+        // A 1.1 file (without checksums), whose record claims 1000 bytes but
+        // has only 37.
+        let mut data = utils::FILE_TYPE.as_bytes().to_vec();
+        data.extend_from_slice(&[1, 1]);
+        data.extend((0..37).map(|i: u8| i));
+        data.extend_from_slice(&6_u64.to_le_bytes());
+        data.extend_from_slice(&1000_u64.to_le_bytes());
+        data.push(2);
+        data.extend_from_slice(b"c1");
+        data.extend_from_slice(&43_u64.to_le_bytes());
+        data.extend_from_slice(&1_u64.to_le_bytes());
+        std::fs::write(&*packed_file, &data)?;
+        assert!(compact(&*packed_file, &*compacted_file).is_err());
+        assert!(!compacted_file.exists());
+
+        // A record whose data does not match its checksum.
+        pack_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12; 1000])].into_iter(),
+        )?;
+        let mut data = std::fs::read(&*packed_file)?;
+        data[100] = 0x34;
+        std::fs::write(&*packed_file, &data)?;
+        assert!(compact(&*packed_file, &*compacted_file).is_err());
+        assert!(!compacted_file.exists());
+        assert!(compact_in_place(&*packed_file).is_err());
+        assert_eq!(std::fs::read(&*packed_file)?, data);
+        Ok(())
+    }
+
+    #[test]
+    /// A pack that fails leaves the destination as it was, and no temporary
+    /// files around.
+    fn pack_failure_is_atomic() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/atomicpacked.bin")?);
+        pack_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+        )?;
+        let packed = std::fs::read(&*packed_file)?;
+
+        let res = pack_files(
+            &*packed_file,
+            [("c1", &PathBuf::from_str("/tmp/atomicpacked/doesnt/exist")?)].into_iter(),
+        );
+        assert!(matches!(res, Err(EasypackError::IoError(_))));
+        assert_eq!(std::fs::read(&*packed_file)?, packed);
+
+        // The same name twice: the pack fails after some data is written.
+        let res = pack_records(
+            &*packed_file,
+            [
+                utils::Record::new("c2".into(), vec![0x56]),
+                utils::Record::new("c2".into(), vec![0x78]),
+            ]
+            .into_iter(),
+        );
+        assert!(matches!(res, Err(EasypackError::RecordSameName(_))));
+        assert_eq!(std::fs::read(&*packed_file)?, packed);
+
+        assert!(
+            !std::fs::read_dir("/tmp")?.any(|entry| entry.is_ok_and(|entry| entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".atomicpacked.bin.")))
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Packing over a larger archive leaves nothing of it behind, whatever
+    /// the pack function, and so does unpacking over a larger file.
+    fn pack_overwrite_larger_archive() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/overwrite_larger.bin")?);
+        let fresh_file = Tempfile::from_path(PathBuf::from_str("/tmp/overwrite_fresh.bin")?);
+        let infile = Tempfile::from_path(PathBuf::from_str("/tmp/overwrite_input.txt")?);
+        let in_dir = PathBuf::from_str("/tmp/overwrite_dir")?;
+        let _ = std::fs::remove_dir_all(&in_dir);
+        std::fs::create_dir_all(&in_dir)?;
+        std::fs::write(&*infile, [0x12, 0x34])?;
+        std::fs::write(in_dir.join("c1"), [0x12, 0x34])?;
+
+        let pack_large = || {
+            pack_records(
+                &*packed_file,
+                (0..100).map(|i| utils::Record::new(format!("large_{i}"), vec![0x56; 1_000])),
+            )
+        };
+        let check = |packed: &Path| -> std::result::Result<(), Box<dyn std::error::Error>> {
+            let names: Vec<_> = list_entries(packed)?.map(|entry| entry.name).collect();
+            assert_eq!(names, ["c1"]);
+            assert_eq!(verify(packed)?, []);
+            assert_eq!(
+                unpack_records(packed, ["c1"].into_iter())?.0[0].data,
+                [0x12, 0x34]
+            );
+            Ok(())
+        };
+
+        pack_records(
+            &*fresh_file,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+        )?;
+        let fresh_len = std::fs::metadata(&*fresh_file)?.len();
+
+        pack_large()?;
+        pack_records(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+        )?;
+        assert_eq!(std::fs::metadata(&*packed_file)?.len(), fresh_len);
+        check(&packed_file)?;
+
+        pack_large()?;
+        pack_records_with_mode(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+            OpenMode::Truncate,
+        )?;
+        assert_eq!(std::fs::metadata(&*packed_file)?.len(), fresh_len);
+        check(&packed_file)?;
+
+        pack_large()?;
+        pack_files(&*packed_file, [("c1", &*infile)].into_iter())?;
+        check(&packed_file)?;
+
+        pack_large()?;
+        pack_dir(&*packed_file, &in_dir, Symlinks::Error)?;
+        check(&packed_file)?;
+
+        // Nor does unpacking over a larger file.
+        std::fs::write(&*infile, [0x56; 1_000])?;
+        unpack_files(&*packed_file, [("c1", &*infile)].into_iter())?;
+        assert_eq!(std::fs::read(&*infile)?, [0x12, 0x34]);
+
+        std::fs::remove_dir_all(&in_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    /// Packing over a file keeps its permissions, and packing over a symlink
+    /// replaces the file it points to, not the link.
+    fn pack_overwrite_keeps_file() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::PermissionsExt;
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/overwrite_keeps.bin")?);
+        let link = Tempfile::from_path(PathBuf::from_str("/tmp/overwrite_keeps_link.bin")?);
+        let _ = std::fs::remove_file(&*link);
+        std::fs::write(&*packed_file, [0x12])?;
+        std::fs::set_permissions(&*packed_file, std::fs::Permissions::from_mode(0o640))?;
+        // A relative link, resolved from its own directory.
+        std::os::unix::fs::symlink("overwrite_keeps.bin", &*link)?;
+
+        pack_records(
+            &*link,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+        )?;
+        assert!(std::fs::symlink_metadata(&*link)?.file_type().is_symlink());
+        let metadata = std::fs::metadata(&*packed_file)?;
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(list_entries(&*packed_file)?.count(), 1);
+
+        std::fs::remove_file(&*link)?;
+        Ok(())
+    }
+
+    #[test]
+    /// The copy used where hard links are not available never replaces an
+    /// existing file.
+    fn copy_new_file() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let from = Tempfile::from_path(PathBuf::from_str("/tmp/copy_new_from.bin")?);
+        let dest = Tempfile::from_path(PathBuf::from_str("/tmp/copy_new_dest.bin")?);
+        std::fs::write(&*from, [0x12, 0x34])?;
+
+        utils::copy_new(&from, &dest)?;
+        assert_eq!(std::fs::read(&*dest)?, [0x12, 0x34]);
+        std::fs::write(&*from, [0x56])?;
+        let err = utils::copy_new(&from, &dest).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&*dest)?, [0x12, 0x34]);
+        Ok(())
+    }
+
+    #[test]
+    /// With `OpenMode::CreateNew`, an existing file is never touched.
+    fn pack_create_new() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/createnew.bin")?);
+        let infile = Tempfile::from_path(PathBuf::from_str("/tmp/createnew_input.txt")?);
+        std::fs::write(&*infile, [0x12, 0x34])?;
+        let in_dir = PathBuf::from_str("/tmp/createnew_dir")?;
+        let _ = std::fs::remove_dir_all(&in_dir);
+        std::fs::create_dir_all(&in_dir)?;
+
+        pack_records_with_mode(
+            &*packed_file,
+            [utils::Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+            OpenMode::CreateNew,
+        )?;
+        let packed = std::fs::read(&*packed_file)?;
+        assert_eq!(list_entries(&*packed_file)?.count(), 1);
+
+        let is_already_exists = |res: Result<()>| matches!(res, Err(EasypackError::IoError(e)) if e.kind() == std::io::ErrorKind::AlreadyExists);
+        assert!(is_already_exists(pack_records_with_mode(
+            &*packed_file,
+            [utils::Record::new("c2".into(), vec![0x56])].into_iter(),
+            OpenMode::CreateNew,
+        )));
+        assert!(is_already_exists(pack_files_with_mode(
+            &*packed_file,
+            [("c2", &*infile)].into_iter(),
+            OpenMode::CreateNew,
+        )));
+        assert!(is_already_exists(pack_dir_with_mode(
+            &*packed_file,
+            &in_dir,
+            Symlinks::Error,
+            OpenMode::CreateNew,
+        )));
+        assert_eq!(std::fs::read(&*packed_file)?, packed);
+        assert!(
+            !std::fs::read_dir("/tmp")?.any(|entry| entry.is_ok_and(|entry| entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".createnew.bin.")))
+        );
+
+        std::fs::remove_dir_all(&in_dir)?;
+        Ok(())
+    }
+
     #[test]
     /// Update a file, without making a new one.
     fn update_file() -> std::result::Result<(), Box<dyn std::error::Error>> {
@@ -598,4 +2210,55 @@ pub mod test {
 
         Ok(())
     }
+
+    #[test]
+    /// Files of older versions are refused by the updates, and left as they
+    /// are, until they are upgraded explicitly with `compact`.
+    fn update_old_version() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::writers::ver_1_1::Packer;
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/update_old_version.bin")?);
+        let mut writer = Packer::from_writer(BufWriter::new(std::fs::File::create(&*packed_file)?))
+            .write_header()?;
+        writer.write_record(utils::Record::new("c1".to_owned(), vec![0x12, 0x34]))?;
+        writer.write_record(utils::Record::new("c2".to_owned(), vec![0x56]))?;
+        writer.write_record(utils::Record::new("c3".to_owned(), vec![0x78]))?;
+        writer.close()?;
+        let packed = std::fs::read(&*packed_file)?;
+
+        let c4 = || [utils::Record::new("c4".to_owned(), vec![0x9a])].into_iter();
+        let c2 = || [utils::Record::new("c2".to_owned(), vec![0xbc])].into_iter();
+        assert!(matches!(
+            pack_records_update(&*packed_file, c4()),
+            Err(EasypackError::InvalidFileError(_))
+        ));
+        assert!(matches!(
+            replace_records(&*packed_file, c2()),
+            Err(EasypackError::InvalidFileError(_))
+        ));
+        assert!(matches!(
+            remove_records(&*packed_file, ["c3"].into_iter()),
+            Err(EasypackError::InvalidFileError(_))
+        ));
+        assert_eq!(std::fs::read(&*packed_file)?, packed);
+
+        // Once upgraded, the updates work.
+        compact_in_place(&*packed_file)?;
+        let mut file = std::fs::File::open(&*packed_file)?;
+        assert_eq!(
+            readers::read_header(&mut file)?,
+            writers::Format::VERSION.into()
+        );
+        pack_records_update(&*packed_file, c4())?;
+        replace_records(&*packed_file, c2())?;
+        assert!(remove_records(&*packed_file, ["c3"].into_iter())?.is_empty());
+
+        let mut archive = Archive::open(&*packed_file)?;
+        assert_eq!(archive.names().collect::<Vec<_>>(), ["c1", "c2", "c4"]);
+        assert_eq!(archive.get("c1")?.map(|r| r.data), Some(vec![0x12, 0x34]));
+        assert_eq!(archive.get("c2")?.map(|r| r.data), Some(vec![0xbc]));
+        assert_eq!(archive.get("c4")?.map(|r| r.data), Some(vec![0x9a]));
+        assert!(verify(&*packed_file)?.is_empty());
+
+        Ok(())
+    }
 }
@@ -4,7 +4,13 @@ use std::env::args;
 fn help() {
     eprintln!("Usage:");
     eprintln!("  pack `outfile` `data name 1` `infile 1` [...]");
+    eprintln!("  pack-dir `outfile` `dir` [--skip-symlinks]");
     eprintln!("  unpack `infile` `data name 1` `outfile 1` [...]");
+    eprintln!("  extract `infile` `outdir`");
+    eprintln!("  compact `infile` [`outfile`]");
+    eprintln!("  list `infile`");
+    eprintln!("  info `infile`");
+    eprintln!("  verify `infile`");
 }
 
 fn pack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
@@ -14,7 +20,7 @@ fn pack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     }
     let (outfile, args) = args.split_at(1);
 
-    match outfile.get(0) {
+    match outfile.first() {
         Some(outfile) => {
             if args.is_empty() || args.len() % 2 != 0 {
                 help();
@@ -24,7 +30,7 @@ fn pack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
             // I can unwrap since I've already checked this is ok.
             let slice = args
                 .chunks(2)
-                .map(|v| (v.get(0).unwrap(), v.get(1).unwrap()));
+                .map(|v| (v.first().unwrap(), v.get(1).unwrap()));
             pack_files(outfile, slice)?;
         }
         None => return Err("`outfile` not provided".into()),
@@ -33,6 +39,21 @@ fn pack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn pack_directory(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args {
+        [outfile, dir] => pack_dir(outfile, dir, Symlinks::Error)?,
+        [outfile, dir, flag] if flag == "--skip-symlinks" => {
+            pack_dir(outfile, dir, Symlinks::Skip)?;
+        }
+        _ => {
+            help();
+            return Err("Arguments must be `outfile` `dir` [--skip-symlinks]".into());
+        }
+    }
+
+    Ok(())
+}
+
 fn unpack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     if args.is_empty() {
         help();
@@ -40,7 +61,7 @@ fn unpack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     }
     let (infile, args) = args.split_at(1);
 
-    match infile.get(0) {
+    match infile.first() {
         Some(infile) => {
             if args.is_empty() || args.len() % 2 != 0 {
                 help();
@@ -50,7 +71,7 @@ fn unpack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
             // I can unwrap since I've already checked this is ok.
             let slice = args
                 .chunks(2)
-                .map(|v| (v.get(0).unwrap(), v.get(1).unwrap()));
+                .map(|v| (v.first().unwrap(), v.get(1).unwrap()));
             let unable_to_unpack = unpack_files(infile, slice)?;
             if !unable_to_unpack.is_empty() {
                 eprintln!("Not found in input file:");
@@ -65,14 +86,127 @@ fn unpack(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Offers 2 options:
+fn extract(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args {
+        [infile, outdir] => unpack_all(infile, outdir)?,
+        _ => {
+            help();
+            return Err("Arguments must be `infile` `outdir`".into());
+        }
+    }
+
+    Ok(())
+}
+
+fn compact_file(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let reclaimed = match args {
+        [infile] => compact_in_place(infile)?,
+        [infile, outfile] => compact(infile, outfile)?,
+        _ => {
+            help();
+            return Err("Arguments must be `infile` [`outfile`]".into());
+        }
+    };
+    println!("{reclaimed} bytes reclaimed");
+
+    Ok(())
+}
+
+fn list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args {
+        [infile] => {
+            for entry in list_entries(infile)? {
+                if entry.compression == Compression::None {
+                    println!(
+                        "{}\t{} bytes\tat {}",
+                        entry.name, entry.data_len, entry.data_start
+                    );
+                } else {
+                    println!(
+                        "{}\t{} bytes\tat {}\t{}, {} bytes uncompressed",
+                        entry.name,
+                        entry.data_len,
+                        entry.data_start,
+                        entry.compression,
+                        entry.uncompressed_len
+                    );
+                }
+            }
+        }
+        _ => {
+            help();
+            return Err("Arguments must be `infile`".into());
+        }
+    }
+
+    Ok(())
+}
+
+fn info(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args {
+        [infile] => {
+            for (key, value) in read_archive_metadata(infile)?.iter() {
+                println!("{key}\t{value}");
+            }
+        }
+        _ => {
+            help();
+            return Err("Arguments must be `infile`".into());
+        }
+    }
+
+    Ok(())
+}
+
+fn verify(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args {
+        [infile] => {
+            let problems = easypack::verify(infile)?;
+            if !problems.is_empty() {
+                eprintln!("Problems found in {infile}:");
+                for problem in &problems {
+                    eprintln!("- {problem}");
+                }
+                return Err(format!("{infile} is corrupted.").into());
+            }
+            println!("{infile}: ok");
+        }
+        _ => {
+            help();
+            return Err("Arguments must be `infile`".into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Offers 8 options:
 /// - pack `outputfile` `data name 1` `file 1` [...]
 ///   The pack command accepts an outputfile and at least a couple which
 ///   identifies the name we want to store for this record, and the file where
 ///   we take the data from;
+/// - pack-dir `outputfile` `dir` [--skip-symlinks]
+///   packs all the files in the directory and its subdirectories, using their
+///   relative paths as names; symlinks are an error, unless they are skipped
 /// - unpack `inputfile` `data name 1` `file 1` [...]
 ///   accpepts an input (packed) file and a series of
 ///   `name of the input data` + `where to store it`
+/// - extract `inputfile` `outdir`
+///   unpacks all the records of the input (packed) file in the output
+///   directory, using their names as relative paths
+/// - compact `inputfile` [`outputfile`]
+///   copies only the live records of the input (packed) file into the output
+///   file, or in the input file itself if no output file is given, and prints
+///   how many bytes were reclaimed
+/// - list `inputfile`
+///   prints the name, size and offset of every record in the input (packed)
+///   file
+/// - info `inputfile`
+///   prints the metadata of the input (packed) file, one key and value per
+///   line
+/// - verify `inputfile`
+///   checks that the input (packed) file is sound, and reports all the
+///   problems found in it otherwise
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<_> = args().skip(1).collect();
     if args.is_empty() {
@@ -80,10 +214,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Err("Not enough arguments.".into());
     }
     let (command, args) = args.split_at(1);
-    match command.get(0) {
+    match command.first() {
         Some(v) => match v.as_str() {
             "pack" => pack(args)?,
+            "pack-dir" => pack_directory(args)?,
             "unpack" => unpack(args)?,
+            "extract" => extract(args)?,
+            "compact" => compact_file(args)?,
+            "list" => list(args)?,
+            "info" => info(args)?,
+            "verify" => verify(args)?,
             _ => {
                 help();
                 return Err(format!("Unkown command: {}", v).into());
@@ -0,0 +1,163 @@
+//! The metadata of the records, since version 1.5.
+//! Every record has a set of typed key/value pairs: some keys are well known
+//! (the modification time, the permissions, and the content type, see the
+//! constants of `Metadata`), but any other can be used.
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+/// A single metadata value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataValue {
+    /// An unsigned number.
+    U64(u64),
+    /// Some utf8 text.
+    Text(String),
+}
+
+impl MetadataValue {
+    #[must_use]
+    /// The code of the type of the value, as stored in the `ToC`.
+    pub const fn code(&self) -> u8 {
+        match self {
+            Self::U64(_) => 0,
+            Self::Text(_) => 1,
+        }
+    }
+}
+
+impl core::fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::U64(value) => write!(f, "{value}"),
+            Self::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+/**
+The metadata of a record: a set of key/value pairs, sorted by key.
+
+# Usage.
+
+```
+use easypack::*;
+
+let metadata = Metadata::new()
+    .with_content_type("text/plain")
+    .with_value("author", MetadataValue::Text("me".into()));
+let record = Record::new("c1".into(), b"Some text".to_vec()).with_metadata(metadata);
+assert_eq!(record.metadata.content_type(), Some("text/plain"));
+assert_eq!(record.metadata.mtime(), None);
+```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    values: BTreeMap<String, MetadataValue>,
+}
+
+impl Metadata {
+    /// The key of the modification time, in seconds since the Unix epoch, as
+    /// a `MetadataValue::U64`.
+    pub const MTIME: &'static str = "mtime";
+    /// The key of the Unix permissions (e.g. `0o644`), as a
+    /// `MetadataValue::U64`.
+    pub const MODE: &'static str = "mode";
+    /// The key of the MIME type of the data (e.g. `text/plain`), as a
+    /// `MetadataValue::Text`.
+    pub const CONTENT_TYPE: &'static str = "content-type";
+
+    #[must_use]
+    /// Empty metadata.
+    pub const fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+        }
+    }
+
+    #[must_use]
+    /// The value associated with `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.values.get(key)
+    }
+
+    /// Associate `value` with `key`, replacing the old value if any.
+    pub fn insert(&mut self, key: impl Into<String>, value: MetadataValue) {
+        self.values.insert(key.into(), value);
+    }
+
+    #[must_use]
+    /// The same metadata, with `value` associated with `key`.
+    pub fn with_value(mut self, key: impl Into<String>, value: MetadataValue) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Remove the value associated with `key`, if any.
+    pub fn remove(&mut self, key: &str) -> Option<MetadataValue> {
+        self.values.remove(key)
+    }
+
+    /// All the pairs, sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MetadataValue)> {
+        self.values.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    #[must_use]
+    /// The number of pairs.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[must_use]
+    /// Whether there are no pairs at all.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[must_use]
+    /// The modification time, in seconds since the Unix epoch, if any.
+    pub fn mtime(&self) -> Option<u64> {
+        self.u64(Self::MTIME)
+    }
+
+    #[must_use]
+    /// The same metadata, with the modification time in seconds since the
+    /// Unix epoch.
+    pub fn with_mtime(self, mtime: u64) -> Self {
+        self.with_value(Self::MTIME, MetadataValue::U64(mtime))
+    }
+
+    #[must_use]
+    /// The Unix permissions, if any.
+    pub fn mode(&self) -> Option<u32> {
+        self.u64(Self::MODE).and_then(|mode| mode.try_into().ok())
+    }
+
+    #[must_use]
+    /// The same metadata, with the Unix permissions.
+    pub fn with_mode(self, mode: u32) -> Self {
+        self.with_value(Self::MODE, MetadataValue::U64(mode.into()))
+    }
+
+    #[must_use]
+    /// The MIME type of the data, if any.
+    pub fn content_type(&self) -> Option<&str> {
+        match self.get(Self::CONTENT_TYPE) {
+            Some(MetadataValue::Text(content_type)) => Some(content_type),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    /// The same metadata, with the MIME type of the data.
+    pub fn with_content_type(self, content_type: impl Into<String>) -> Self {
+        self.with_value(Self::CONTENT_TYPE, MetadataValue::Text(content_type.into()))
+    }
+
+    fn u64(&self, key: &str) -> Option<u64> {
+        match self.get(key) {
+            Some(MetadataValue::U64(value)) => Some(*value),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! A packed file mapped in memory.
+//! Records are handed out as slices of the mapped file, so reading them needs
+//! neither system calls nor allocations.
+use std::borrow::Cow;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use memmap2::Mmap;
+
+use crate::error::Result;
+use crate::metadata::Metadata;
+use crate::readers::{self, Entry, Toc};
+
+/**
+A packed file mapped in memory, whatever its (supported) version is.
+
+The `ToC` is read once, when the file is opened. Afterwards, every record is a
+slice borrowed from the mapped file, unless it is compressed.
+
+The first `get` of every record checks its data against its checksum, if
+any. The later ones trust it, since the file can't change while it is mapped
+(see `open`): they only decompress it, if needed.
+
+# Usage.
+
+```
+use easypack::*;
+# use std::path::PathBuf;
+# use std::str::FromStr;
+
+# let packed_data_file = PathBuf::from_str("/tmp/__mmap_doc__.bin").unwrap();
+pack_records(
+    &packed_data_file,
+    [
+        Record::new("c1".into(), vec![0x12, 0x34]),
+        Record::new("c2".into(), vec![0x34]),
+    ]
+    .into_iter(),
+).unwrap();
+
+// Nothing modifies the file while it is mapped.
+let archive = unsafe { MmapArchive::open(&packed_data_file) }.unwrap();
+assert_eq!(archive.get("c2").unwrap().as_deref(), Some([0x34].as_slice()));
+assert!(archive.get("nope").unwrap().is_none());
+# std::fs::remove_file(&packed_data_file).unwrap();
+```
+*/
+pub struct MmapArchive {
+    map: Mmap,
+    toc: Toc,
+    // Whether each entry of the `ToC` already matched its checksum.
+    verified: Vec<AtomicBool>,
+}
+
+impl MmapArchive {
+    /// Map the packed file `infile` in memory, and read its `ToC`.
+    /// # Errors
+    /// Any IO error, or if the file is not a valid packed file.
+    /// # Safety
+    /// The file must not be modified while it is mapped, that is until the
+    /// archive is dropped: the content of the records would change under our
+    /// feet, and if the file is truncated, reading them would crash the
+    /// process. The pack functions write a new file which then replaces the
+    /// old one, so they are fine, but the updates of this crate
+    /// (`pack_records_update`, `remove_records` and `replace_records`) append
+    /// to the file in place: they must not run while the file is mapped.
+    /// Neither must other processes modify it.
+    pub unsafe fn open(infile: impl AsRef<Path>) -> Result<Self> {
+        let infile = OpenOptions::new().create(false).read(true).open(&infile)?;
+        // Safety: the caller guarantees that the file is not modified.
+        let map = unsafe { Mmap::map(&infile)? };
+        let toc = readers::parser::read_toc(&map)?;
+        let verified = toc.iter().map(|_| AtomicBool::new(false)).collect();
+        Ok(Self { map, toc, verified })
+    }
+
+    /// The data of the record associated with `name`, if any.
+    /// It is borrowed from the mapped file, unless the record is compressed.
+    /// Its checksum is only computed at the first call.
+    /// # Errors
+    /// If the record is not inside the file, it does not match its checksum,
+    /// or it can't be decompressed.
+    pub fn get(&self, name: &str) -> Result<Option<Cow<'_, [u8]>>> {
+        let Some((i, entry)) = self.toc.find(name) else {
+            return Ok(None);
+        };
+        let record = readers::entry_bytes(&self.map, entry)?;
+        if !self.verified[i].load(Ordering::Relaxed) {
+            readers::verify_entry(record, entry)?;
+            self.verified[i].store(true, Ordering::Relaxed);
+        }
+        readers::record_data(record, entry).map(Some)
+    }
+
+    #[must_use]
+    /// Whether there is a record associated with `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.toc.contains(name)
+    }
+
+    #[must_use]
+    /// The number of records.
+    pub const fn len(&self) -> usize {
+        self.toc.len()
+    }
+
+    #[must_use]
+    /// Whether there are no records at all.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The names of all the records, in the order they are stored.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.toc.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// The entries of all the records, in the order they are stored.
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.toc.iter()
+    }
+
+    #[must_use]
+    /// The metadata of the archive, empty for the versions before 1.6.
+    pub const fn metadata(&self) -> &Metadata {
+        self.toc.metadata()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test::Tempfile;
+    use crate::utils::Record;
+    use crate::EasypackError;
+
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    #[test]
+    /// Records are read from the mapped file.
+    fn mmap_archive() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/mmap_archive.bin")?);
+        crate::pack_records(
+            &*packed_file,
+            [
+                Record::new("c1".into(), vec![0x12, 0x34]),
+                Record::new("c2".into(), vec![]),
+            ]
+            .into_iter(),
+        )?;
+
+        // Nothing modifies the file while it is mapped.
+        let archive = unsafe { MmapArchive::open(&*packed_file)? };
+        assert_eq!(archive.len(), 2);
+        assert!(archive.contains("c1"));
+        assert_eq!(archive.names().collect::<Vec<_>>(), ["c1", "c2"]);
+        assert_eq!(archive.get("c1")?.as_deref(), Some([0x12, 0x34].as_slice()));
+        assert_eq!(archive.get("c2")?.as_deref(), Some([].as_slice()));
+        assert!(archive.get("nope")?.is_none());
+        // Every record read so far is not verified again.
+        assert!(archive
+            .verified
+            .iter()
+            .all(|verified| verified.load(Ordering::Relaxed)));
+        Ok(())
+    }
+
+    #[test]
+    /// Corrupted records are detected.
+    fn mmap_archive_corrupted() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/mmap_corrupted.bin")?);
+        crate::pack_records(
+            &*packed_file,
+            [Record::new("c1".into(), vec![0x12, 0x34])].into_iter(),
+        )?;
+        let mut data = std::fs::read(&*packed_file)?;
+        data[7] ^= 0x01;
+        std::fs::write(&*packed_file, data)?;
+
+        // Nothing modifies the file while it is mapped.
+        let archive = unsafe { MmapArchive::open(&*packed_file)? };
+        assert!(matches!(
+            archive.get("c1"),
+            Err(EasypackError::ChecksumMismatch(_))
+        ));
+        Ok(())
+    }
+}
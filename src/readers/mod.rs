@@ -1,80 +1,328 @@
-use std::io::{Read, Seek};
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{Read, Seek, SeekFrom};
 
+use crate::checksum::crc32;
+use crate::compression::Compression;
 use crate::error::{EasypackError, Result};
+#[cfg(feature = "std")]
 use crate::utils;
 
-pub mod ver_1_0;
-pub mod ver_1_1;
+pub mod parser;
+#[cfg(feature = "std")]
+mod record_reader;
+mod toc;
 
+#[cfg(feature = "std")]
+pub use record_reader::{ReadSeek, RecordReader};
+pub use toc::{Entry, RawEntry, Toc};
+
+#[cfg(feature = "std")]
 /// The internal trait that defines an unpacker.
-/// Every unpacker is related to a different version, that can be completely
-/// different from every others. That's why we try to keep the unpackers free
-/// to behave as they want.
-pub trait VersionedUnpacker<'r> {
+/// The versions only differ in how the `ToC` is stored, which `parser` takes
+/// care of: once it is read, the work is the same for all of them, and this is
+/// what the provided methods do.
+pub trait VersionedUnpacker {
     /// Initialize the unpacker, if needed.
     /// # Errors
     /// In case the initialization fails.
     fn init(&mut self) -> Result<()>;
+    /// The `ToC`, which is empty until `init` is called.
+    fn toc(&self) -> &Toc;
+    /// The reader to get the data from, together with the `ToC`.
+    fn reader_and_toc(&mut self) -> (&mut dyn ReadSeek, &Toc);
     /// Read the record associated with `record_name`, if any.
     /// # Errors
-    /// In case the record name is too long.
-    fn read_record(&mut self, record_name: &str) -> Result<Option<utils::Record>>;
+    /// In case the record is invalid, or its data does not match its checksum.
+    fn read_record(&mut self, record_name: &str) -> Result<Option<utils::Record>> {
+        let (reader, toc) = self.reader_and_toc();
+        match toc.get(record_name) {
+            Some(entry) => {
+                let data = read_entry(reader, entry)?;
+                Ok(Some(
+                    utils::Record::new(record_name.to_owned(), data)
+                        .with_metadata(entry.metadata.clone()),
+                ))
+            }
+            None => Ok(None),
+        }
+    }
+    /// Get a reader over the record associated with `record_name`, if any.
+    /// # Errors
+    /// In case we can't move to the record's data.
+    fn open_record(&mut self, record_name: &str) -> Result<Option<RecordReader<'_>>> {
+        let (reader, toc) = self.reader_and_toc();
+        toc.get(record_name)
+            .map(|entry| RecordReader::new(reader, entry))
+            .transpose()
+    }
+    /// Read `len` bytes of the record associated with `record_name`, if any,
+    /// starting at `offset` in the record.
+    /// Only these bytes are read, so the checksum of the record can't be
+    /// verified, unless the record is compressed: it is then read and
+    /// decompressed as a whole.
+    /// # Errors
+    /// If the range does not fit in the record, or we are unable to read it.
+    fn read_record_range(
+        &mut self,
+        record_name: &str,
+        offset: u64,
+        len: usize,
+    ) -> Result<Option<Vec<u8>>> {
+        let (reader, toc) = self.reader_and_toc();
+        let Some(entry) = toc.get(record_name) else {
+            return Ok(None);
+        };
+        let end = offset.checked_add(len.try_into()?);
+        if end.is_none_or(|end| end > entry.uncompressed_len) {
+            return Err(EasypackError::InvalidRange(format!(
+                "Record {record_name} is {} bytes long, unable to read {len} bytes at {offset}",
+                entry.uncompressed_len
+            )));
+        }
+        if entry.compression != Compression::None {
+            let data = read_entry(reader, entry)?;
+            // Checked above to be in the record.
+            #[allow(clippy::cast_possible_truncation)]
+            let offset = offset as usize;
+            return Ok(Some(data[offset..offset + len].to_vec()));
+        }
+        let start = entry.data_start.checked_add(offset).ok_or_else(|| {
+            EasypackError::InvalidFileError(format!(
+                "Record {record_name} starts at {}, unable to read at {offset}",
+                entry.data_start
+            ))
+        })?;
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Some(read_data(reader, len as u64, record_name)?))
+    }
     /// Inspect the `ToC` from the file.
     /// # Errors
     /// In the input file is invalid.
-    fn inspect_toc(
-        &mut self,
-        f: &mut dyn for<'a, 'b, 'c> FnMut(&'a u64, &'b u64, &'c std::string::String),
-    ) -> Result<()>;
+    fn inspect_toc(&mut self, f: &mut dyn FnMut(&Entry)) -> Result<()> {
+        self.toc().iter().for_each(f);
+        Ok(())
+    }
+    /// Whether a record named `record_name` is in the `ToC`.
+    fn contains(&self, record_name: &str) -> bool {
+        self.toc().contains(record_name)
+    }
+    /// The number of records in the `ToC`.
+    fn len(&self) -> usize {
+        self.toc().len()
+    }
+    /// The names of all the records in the `ToC`, in the order they are stored.
+    fn names(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.toc().iter().map(|entry| entry.name.as_str()))
+    }
 }
 
+#[cfg(feature = "std")]
 /// Read the header, and get the version out (maj, min)
+/// # Errors
+/// If the file does not start with a valid header.
 pub fn read_header<R: Read + Seek>(r: &mut R) -> Result<utils::Version> {
     r.rewind()?;
-    let mut buf = vec![0; 4];
-    if r.read(&mut buf[..4])? != 4 {
-        return Err(EasypackError::InvalidFileError(
-            "Not enough bytes in the header".to_owned(),
-        ));
+    let mut header = Vec::new();
+    r.take(utils::HEADER_SIZE).read_to_end(&mut header)?;
+    parser::read_header(&header)
+}
+
+#[cfg(feature = "std")]
+/// A reader, as a `Source` of the packed data for the `parser`: the bytes
+/// are read from it when they are needed.
+pub struct IoSource<R>(pub R);
+
+#[cfg(feature = "std")]
+impl<'s, R: Read + Seek> parser::Source<'s> for IoSource<R> {
+    fn size(&mut self) -> Result<u64> {
+        Ok(self.0.seek(SeekFrom::End(0))?)
     }
-    let header = String::from_utf8(buf[..4].to_vec())
-        .map_err(|e| EasypackError::InvalidFileError(format!("Unable to read the header: {e}")))?;
-    if header.as_bytes() != utils::FILE_TYPE.as_bytes() {
+
+    fn read_at(&mut self, pos: u64, len: u64) -> Result<Option<Cow<'s, [u8]>>> {
+        self.0.seek(SeekFrom::Start(pos))?;
+        // `len` comes from the file: as in `read_data`, the buffer only grows
+        // with the data which is actually there.
+        let mut data = Vec::new();
+        (&mut self.0).take(len).read_to_end(&mut data)?;
+        Ok((data.len() as u64 == len).then_some(Cow::Owned(data)))
+    }
+}
+
+#[cfg(feature = "std")]
+// Read the `len` bytes of the record `record_name` at the current position of
+// `r`. `len` comes from the file, so the buffer only grows with the data which
+// is actually there, instead of being allocated up front.
+fn read_data<R: Read + ?Sized>(r: &mut R, len: u64, record_name: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    r.take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
         return Err(EasypackError::InvalidFileError(format!(
-            "Header does not match, found {header}"
+            "Not enough bytes to read record {record_name}"
         )));
     }
-    if r.read(&mut buf[..2])? != 2 {
-        return Err(EasypackError::InvalidFileError(
-            "Not enough bytes in the version".to_owned(),
-        ));
+    Ok(data)
+}
+
+#[cfg(feature = "std")]
+/// Read the data of the record described by `entry`, verify it against its
+/// checksum if there is one, and decompress it if needed.
+/// # Errors
+/// If the data can't be read, it does not match the checksum, or it can't be
+/// decompressed.
+pub fn read_entry<R: Read + Seek + ?Sized>(r: &mut R, entry: &Entry) -> Result<Vec<u8>> {
+    let len: usize = entry.data_len.try_into().map_err(|_| {
+        EasypackError::RecordTooBig(format!(
+            "Record {} is {} bytes long, which does not fit in memory on this architecture",
+            entry.name, entry.data_len
+        ))
+    })?;
+    r.seek(SeekFrom::Start(entry.data_start))?;
+    let data = read_data(r, len as u64, &entry.name)?;
+    verify_entry(&data, entry)?;
+    if entry.compression != Compression::None {
+        return decompress(&data, entry);
+    }
+    Ok(data)
+}
+
+/// Get the data of the record described by `entry` out of `data`, which is
+/// the whole packed file, and verify it against its checksum if there is one.
+/// # Errors
+/// If the record is not inside `data`, or it does not match the checksum.
+pub fn entry_slice<'a>(data: &'a [u8], entry: &Entry) -> Result<&'a [u8]> {
+    let record = entry_bytes(data, entry)?;
+    verify_entry(record, entry)?;
+    Ok(record)
+}
+
+/// Get the data of the record described by `entry` out of `data`, which is
+/// the whole packed file, as it is stored: nothing is verified.
+/// # Errors
+/// If the record is not inside `data`.
+pub fn entry_bytes<'a>(data: &'a [u8], entry: &Entry) -> Result<&'a [u8]> {
+    usize::try_from(entry.data_start)
+        .ok()
+        .zip(usize::try_from(entry.data_len).ok())
+        .and_then(|(start, len)| data.get(start..start.checked_add(len)?))
+        .ok_or_else(|| {
+            EasypackError::InvalidFileError(format!(
+                "Not enough bytes to read record {}",
+                entry.name
+            ))
+        })
+}
+
+/// Verify `record`, the data of the record described by `entry` as it is
+/// stored, against its checksum if there is one.
+/// # Errors
+/// If it does not match the checksum.
+pub fn verify_entry(record: &[u8], entry: &Entry) -> Result<()> {
+    if let Some(checksum) = entry.checksum {
+        let actual = crc32(record);
+        if actual != checksum {
+            return Err(EasypackError::ChecksumMismatch(format!(
+                "Record {} has checksum {actual:#010x}, expected {checksum:#010x}",
+                entry.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Get the data of the record described by `entry` out of `data`, as
+/// `entry_slice` does, and decompress it if needed. Only compressed records
+/// are copied, the others are borrowed from `data`.
+/// # Errors
+/// If the record is not inside `data`, it does not match the checksum, or it
+/// can't be decompressed.
+pub fn entry_data<'a>(data: &'a [u8], entry: &Entry) -> Result<Cow<'a, [u8]>> {
+    record_data(entry_slice(data, entry)?, entry)
+}
+
+/// Decompress `record`, the data of the record described by `entry` as it is
+/// stored, if needed. It is borrowed if it is not compressed.
+/// # Errors
+/// If it can't be decompressed.
+pub fn record_data<'a>(record: &'a [u8], entry: &Entry) -> Result<Cow<'a, [u8]>> {
+    if entry.compression == Compression::None {
+        Ok(Cow::Borrowed(record))
+    } else {
+        decompress(record, entry).map(Cow::Owned)
+    }
+}
+
+// Decompress `data`, the compressed data of the record described by `entry`.
+fn decompress(data: &[u8], entry: &Entry) -> Result<Vec<u8>> {
+    let len = usize::try_from(entry.uncompressed_len).map_err(|_| {
+        EasypackError::RecordTooBig(format!(
+            "Record {} is {} bytes long, which does not fit in memory on this architecture",
+            entry.name, entry.uncompressed_len
+        ))
+    })?;
+    entry.compression.decompress(data, len)
+}
+
+#[cfg(feature = "std")]
+/// The unpacker, which can be used to read data from the given reader,
+/// whatever its version.
+pub struct Unpacker<R: Read + Seek> {
+    reader: R,
+    version: utils::Version,
+    toc: Toc,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> VersionedUnpacker for Unpacker<R> {
+    fn init(&mut self) -> Result<()> {
+        self.toc = parser::read_versioned_toc(&mut IoSource(&mut self.reader), &self.version)?;
+        Ok(())
+    }
+
+    fn toc(&self) -> &Toc {
+        &self.toc
+    }
+
+    fn reader_and_toc(&mut self) -> (&mut dyn ReadSeek, &Toc) {
+        (&mut self.reader, &self.toc)
     }
-    // Unwrap is ok here since I'm checking that I have 2 values above.
-    #[allow(clippy::get_first)]
-    let v1 = [*buf.get(0).unwrap(); 1];
-    let v2 = [*buf.get(1).unwrap(); 1];
-    let v1 = u8::from_le_bytes(v1);
-    let v2 = u8::from_le_bytes(v2);
-    let version = utils::Version::from((v1, v2));
-    Ok(version)
-}
-
-/// Read the version from the header, if possible.
-pub fn get_unpacker<'r, R: Read + Seek>(
-    r: &'r mut R,
-) -> Result<Box<dyn VersionedUnpacker<'r> + 'r>> {
-    let version = read_header(r)?;
-
-    match version.into() {
-        (1, 0) => Ok(Box::new(ver_1_0::Unpacker::from_reader(r))),
-        (1, 1) => Ok(Box::new(ver_1_1::Unpacker::from_reader(r))),
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> Unpacker<R> {
+    #[must_use]
+    /// Create an `Unpacker`, using the given reader, which is read as the
+    /// given `version`.
+    pub fn from_reader(reader: R, version: utils::Version) -> Self {
+        Self {
+            reader,
+            version,
+            toc: Toc::default(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+/// Read the version from the header, if possible, and get the related
+/// unpacker. The unpacker takes ownership of the reader.
+/// # Errors
+/// If the header can't be read, or the version is not supported.
+pub fn get_unpacker<'r, R: Read + Seek + Send + 'r>(
+    mut r: R,
+) -> Result<Box<dyn VersionedUnpacker + Send + 'r>> {
+    let version = read_header(&mut r)?;
+
+    match version.clone().into() {
+        (1, 0..=7) => Ok(Box::new(Unpacker::from_reader(r, version))),
         el => Err(EasypackError::InvalidFileError(format!(
             "Found version `{el:?}`, which is not supported."
         ))),
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
@@ -128,6 +376,44 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    /// Metadata values of unknown types are skipped, known ones are kept.
+    fn read_metadata_unknown_type() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut toc = vec![];
+        toc.extend_from_slice(&6_u64.to_le_bytes());
+        toc.extend_from_slice(&0_u64.to_le_bytes());
+        toc.extend_from_slice(&crc32(&[]).to_le_bytes());
+        toc.push(0);
+        toc.extend_from_slice(&0_u64.to_le_bytes());
+        toc.extend_from_slice(&2_u16.to_le_bytes());
+        // A value of a type from the future.
+        toc.push(3);
+        toc.extend_from_slice(b"new");
+        toc.push(42);
+        toc.extend_from_slice(&3_u32.to_le_bytes());
+        toc.extend_from_slice(&[0x12, 0x34, 0x56]);
+        toc.push(5);
+        toc.extend_from_slice(b"mtime");
+        toc.push(0);
+        toc.extend_from_slice(&8_u32.to_le_bytes());
+        toc.extend_from_slice(&1_234_u64.to_le_bytes());
+        toc.push(4);
+        toc.extend_from_slice(b"name");
+
+        let version = (1, 5).into();
+        let entries = parser::parse_toc(&toc, &version, 1)?.entries;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, b"name");
+        assert_eq!(entries[0].metadata.len(), 1);
+        assert_eq!(entries[0].metadata.mtime(), Some(1_234));
+
+        // Cut anywhere, the entry is an error.
+        for len in 0..toc.len() {
+            assert!(parser::parse_toc(&toc[..len], &version, 1).is_err());
+        }
+        Ok(())
+    }
+
     #[test]
     fn unkown_version() -> std::result::Result<(), Box<dyn std::error::Error>> {
         let mut buff = Cursor::new(vec![]);
@@ -145,6 +431,153 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    /// A range of a record can be read, whatever the version, as long as it
+    /// fits in the record.
+    fn read_record_range() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let data: Vec<u8> = (0..100).collect();
+        let mut buffs = vec![Cursor::new(vec![]), Cursor::new(vec![])];
+        {
+            let buffwriter = BufWriter::new(&mut buffs[0]);
+            let mut writer =
+                crate::writers::ver_1_0::Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(utils::Record::new("c1".to_owned(), data.clone()))?;
+            writer.close()?;
+        }
+        {
+            let buffwriter = BufWriter::new(&mut buffs[1]);
+            let mut writer =
+                crate::writers::ver_1_1::Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(utils::Record::new("c1".to_owned(), data.clone()))?;
+            writer.close()?;
+        }
+        let mut writer = crate::writers::Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        writer.write_compressed_record(
+            utils::Record::new("c1".to_owned(), data.clone()),
+            crate::Compression::Lz,
+        )?;
+        buffs.push(writer.close()?.0);
+
+        for buff in buffs {
+            let mut unpacker = get_unpacker(buff)?;
+            unpacker.init()?;
+            assert_eq!(
+                unpacker.read_record_range("c1", 40, 3)?,
+                Some(vec![40, 41, 42])
+            );
+            assert_eq!(
+                unpacker.read_record_range("c1", 0, 100)?,
+                Some(data.clone())
+            );
+            assert_eq!(unpacker.read_record_range("c1", 100, 0)?, Some(vec![]));
+            assert_eq!(unpacker.read_record_range("c2", 0, 1)?, None);
+            for (offset, len) in [(99, 2), (101, 0), (u64::MAX, 1)] {
+                assert!(matches!(
+                    unpacker.read_record_range("c1", offset, len),
+                    Err(EasypackError::InvalidRange(_))
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// A record starting too far to read a range of it is an error, not a
+    /// panic.
+    fn read_record_range_overflow() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        // A 1.1 file, whose only record starts near `u64::MAX`.
+        let mut data = utils::FILE_TYPE.as_bytes().to_vec();
+        data.extend_from_slice(&[1, 1]);
+        data.extend_from_slice(&(u64::MAX - 2).to_le_bytes());
+        data.extend_from_slice(&10_u64.to_le_bytes());
+        data.push(2);
+        data.extend_from_slice(b"c1");
+        data.extend_from_slice(&6_u64.to_le_bytes());
+        data.extend_from_slice(&1_u64.to_le_bytes());
+
+        let mut unpacker = get_unpacker(Cursor::new(data))?;
+        unpacker.init()?;
+        assert!(matches!(
+            unpacker.read_record_range("c1", 5, 1),
+            Err(EasypackError::InvalidFileError(_))
+        ));
+        let mut reader = unpacker.open_record("c1")?.unwrap();
+        let err = reader.seek(SeekFrom::Start(5)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test]
+    /// Reading the `ToC` reads it only once from the file, while checking the
+    /// footer.
+    fn read_toc_once() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        // Count the bytes read through it.
+        struct Counting<R> {
+            inner: R,
+            read: u64,
+        }
+        impl<R: Read> Read for Counting<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let read = self.inner.read(buf)?;
+                self.read += read as u64;
+                Ok(read)
+            }
+        }
+        impl<R: Seek> Seek for Counting<R> {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        let mut writer = crate::writers::Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        for i in 0..100_u8 {
+            writer.write_record(utils::Record::new(format!("record_{i}"), vec![i]))?;
+        }
+        let (buff, summary) = writer.close()?;
+        let toc_len = summary.total_size - summary.toc_position - parser::CHECKED_FOOTER_SIZE;
+
+        let mut counting = Counting {
+            inner: buff,
+            read: 0,
+        };
+        let mut unpacker = get_unpacker(&mut counting)?;
+        unpacker.init()?;
+        assert_eq!(unpacker.len(), 100);
+        drop(unpacker);
+        assert_eq!(
+            counting.read,
+            utils::HEADER_SIZE + toc_len + parser::CHECKED_FOOTER_SIZE
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// A record longer than the whole file is an error, found without
+    /// allocating its claimed size first.
+    fn read_record_past_end() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        // A 1.1 file, whose only record claims to be `1 << 60` bytes long.
+        let mut data = utils::FILE_TYPE.as_bytes().to_vec();
+        data.extend_from_slice(&[1, 1]);
+        data.extend_from_slice(&6_u64.to_le_bytes());
+        data.extend_from_slice(&(1_u64 << 60).to_le_bytes());
+        data.push(2);
+        data.extend_from_slice(b"c1");
+        data.extend_from_slice(&6_u64.to_le_bytes());
+        data.extend_from_slice(&1_u64.to_le_bytes());
+
+        let mut unpacker = get_unpacker(Cursor::new(data))?;
+        unpacker.init()?;
+        assert!(matches!(
+            unpacker.read_record("c1"),
+            Err(EasypackError::InvalidFileError(_))
+        ));
+        assert!(matches!(
+            unpacker.read_record_range("c1", 0, usize::try_from(u32::MAX)?),
+            Err(EasypackError::InvalidFileError(_))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn wrong_header() -> std::result::Result<(), Box<dyn std::error::Error>> {
         let mut buff = Cursor::new(vec![]);
@@ -0,0 +1,683 @@
+//! The parser of the packed data, shared by all the readers: the header, the
+//! footer and the `ToC` of every version are parsed here, and only here.
+//! The bytes come from a `Source`, which is either the packed data already in
+//! memory, without any need for `std`, or a file (see `readers::IoSource`).
+//!
+//! Compared to the previous version:
+//! - 1.1: the positions and the sizes are u64 instead of u32.
+//! - 1.2: every entry in the `ToC` has the checksum of its data, which is
+//!   verified when the record is read.
+//! - 1.3: the footer can be checked. If the one at the end of the file is not
+//!   valid (e.g. an update was interrupted), the file is read as it was
+//!   before, using the last valid footer.
+//! - 1.4: every entry in the `ToC` tells how its data is compressed, and how
+//!   big it is once decompressed.
+//! - 1.5: every entry in the `ToC` has the metadata of its record.
+//! - 1.6: the `ToC` starts with the metadata of the archive.
+//! - 1.7: the size of the names in the `ToC` is a u32, so that names can be
+//!   longer than 255 bytes.
+use alloc::borrow::{Cow, ToOwned};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::checksum::crc32;
+use crate::compression::Compression;
+use crate::error::{EasypackError, Result};
+use crate::metadata::{Metadata, MetadataValue};
+use crate::utils;
+
+use super::{RawEntry, Toc};
+
+/// Where the packed data is read from.
+pub trait Source<'s> {
+    /// The size of the whole packed data.
+    /// # Errors
+    /// Any IO error.
+    fn size(&mut self) -> Result<u64>;
+    /// The `len` bytes starting at `pos`, if there are that many.
+    /// # Errors
+    /// Any IO error.
+    fn read_at(&mut self, pos: u64, len: u64) -> Result<Option<Cow<'s, [u8]>>>;
+}
+
+impl<'s> Source<'s> for &'s [u8] {
+    fn size(&mut self) -> Result<u64> {
+        Ok(u64::try_from(self.len())?)
+    }
+
+    fn read_at(&mut self, pos: u64, len: u64) -> Result<Option<Cow<'s, [u8]>>> {
+        let data: &'s [u8] = self;
+        Ok(usize::try_from(pos)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(pos, len)| data.get(pos..pos.checked_add(len)?))
+            .map(Cow::Borrowed))
+    }
+}
+
+/// What the footer says, whatever the version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footer {
+    /// Where the `ToC` starts.
+    pub toc_position: u64,
+    /// The number of entries in the `ToC`.
+    pub how_many: u64,
+    /// Where the footer starts.
+    /// This is the end of the file, minus the size of the footer, unless the
+    /// file has been left half written by an update (see version 1.3).
+    pub start: u64,
+    /// The size of the footer itself.
+    pub size: u64,
+}
+
+/// A `ToC`, once parsed.
+pub struct ParsedToc {
+    /// The metadata of the archive, empty before version 1.6.
+    pub metadata: Metadata,
+    /// The entries, in the order they are stored.
+    pub entries: Vec<RawEntry>,
+    /// How many bytes the `ToC` takes. The bytes after them are not part of
+    /// it.
+    #[cfg(feature = "std")]
+    pub len: usize,
+}
+
+// The fields of the packed data, read one after the other.
+struct Fields<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    // The next `len` bytes, for the `field` of the `i`th entry of the `ToC`.
+    fn take(&mut self, len: usize, i: u64, field: &str) -> Result<&'a [u8]> {
+        self.take_or(len, || format!("the {field} of the {i}th toc"))
+    }
+
+    // The next `len` bytes, for what `what` tells.
+    fn take_or(&mut self, len: usize, what: impl FnOnce() -> String) -> Result<&'a [u8]> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| {
+                EasypackError::InvalidFileError(format!("Not enough bytes to read {}", what()))
+            })?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self, what: impl FnOnce() -> String) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+        buf.copy_from_slice(self.take_or(N, what)?);
+        Ok(buf)
+    }
+
+    fn u64(&mut self, i: u64, field: &str) -> Result<u64> {
+        Ok(u64::from_le_bytes(
+            self.take_array(|| format!("the {field} of the {i}th toc"))?,
+        ))
+    }
+
+    fn u32(&mut self, i: u64, field: &str) -> Result<u32> {
+        Ok(u32::from_le_bytes(
+            self.take_array(|| format!("the {field} of the {i}th toc"))?,
+        ))
+    }
+
+    fn u8(&mut self, i: u64, field: &str) -> Result<u8> {
+        Ok(u8::from_le_bytes(
+            self.take_array(|| format!("the {field} of the {i}th toc"))?,
+        ))
+    }
+
+    // The metadata of `owner`, since version 1.5.
+    fn metadata(&mut self, owner: &str) -> Result<Metadata> {
+        let what = |field: &str| format!("the metadata {field} of {owner}");
+        let how_many = u16::from_le_bytes(self.take_array(|| what("len"))?);
+
+        let mut metadata = Metadata::new();
+        for _ in 0..how_many {
+            let [key_len] = self.take_array(|| what("key_len"))?;
+            let key = self.take_or(key_len.into(), || what("key"))?.to_vec();
+            let key = String::from_utf8(key).map_err(|e| {
+                EasypackError::InvalidFileError(format!("A metadata key is not valid utf8: {e}"))
+            })?;
+            let [value_type] = self.take_array(|| what("value_type"))?;
+            let value_len = u32::from_le_bytes(self.take_array(|| what("value_len"))?);
+            let value = self.take_or(value_len.try_into()?, || what(&key))?;
+            let value = match value_type {
+                0 => MetadataValue::U64(u64::from_le_bytes(value.try_into().map_err(|_| {
+                    EasypackError::InvalidFileError(format!(
+                        "Metadata {key} is a u64, but it is {value_len} bytes long"
+                    ))
+                })?)),
+                1 => MetadataValue::Text(String::from_utf8(value.to_vec()).map_err(|e| {
+                    EasypackError::InvalidFileError(format!(
+                        "Metadata {key} is not valid utf8: {e}"
+                    ))
+                })?),
+                // Unknown types are skipped, as the other readers do.
+                _ => continue,
+            };
+            metadata.insert(key, value);
+        }
+        Ok(metadata)
+    }
+}
+
+/// Read the version from the header of `data`.
+/// # Errors
+/// If `data` does not start with a valid header.
+pub fn read_header(data: &[u8]) -> Result<utils::Version> {
+    let header = data.get(..4).ok_or_else(|| {
+        EasypackError::InvalidFileError("Not enough bytes in the header".to_owned())
+    })?;
+    if header != utils::FILE_TYPE.as_bytes() {
+        return Err(EasypackError::InvalidFileError(format!(
+            "Header does not match, found {}",
+            String::from_utf8_lossy(header)
+        )));
+    }
+    match data.get(4..6) {
+        Some(&[maj, min]) => Ok(utils::Version::from((maj, min))),
+        _ => Err(EasypackError::InvalidFileError(
+            "Not enough bytes in the version".to_owned(),
+        )),
+    }
+}
+
+/// Read the whole `ToC` of the packed `data`, whatever its version.
+/// # Errors
+/// If the version is not supported, or the `ToC` can't be read.
+pub fn read_toc(data: &[u8]) -> Result<Toc> {
+    let version = read_header(data)?;
+    let mut source = data;
+    read_versioned_toc(&mut source, &version)
+}
+
+/// Read the whole `ToC` of the packed data in `source`, as the given
+/// `version`.
+/// # Errors
+/// If the version is not supported, or the `ToC` can't be read.
+pub fn read_versioned_toc<'s>(
+    source: &mut impl Source<'s>,
+    version: &utils::Version,
+) -> Result<Toc> {
+    let (footer, toc) = read_footer(source, version)?;
+    let toc = parse_toc(&toc, version, footer.how_many)?;
+    Toc::from_raw(toc.entries).map(|entries| entries.with_metadata(toc.metadata))
+}
+
+/// Read the last valid footer of the packed data in `source`, as the given
+/// `version`, and the bytes of the `ToC` it points to, up to where the footer
+/// starts. Since version 1.3, they are checked against their checksum.
+/// # Errors
+/// If the version is not supported, or there is no valid footer.
+pub fn read_footer<'s>(
+    source: &mut impl Source<'s>,
+    version: &utils::Version,
+) -> Result<(Footer, Cow<'s, [u8]>)> {
+    let size = source.size()?;
+    let (v1, v2) = version.clone().into();
+    let footer_size = match (v1, v2) {
+        (1, 0) => 8,
+        (1, 1 | 2) => 16,
+        (1, 3..=7) => return read_checked_footer(source, size),
+        el => return Err(unsupported(el)),
+    };
+    let start = footer_start(size, footer_size)?;
+    let footer = source.read_at(start, footer_size)?.ok_or_else(|| {
+        EasypackError::InvalidFileError("Not enough bytes in the footer".to_owned())
+    })?;
+    let mut fields = Fields::new(&footer);
+    let (toc_position, how_many) = if v2 == 0 {
+        (
+            fields.u32(0, "footer")?.into(),
+            fields.u32(0, "footer")?.into(),
+        )
+    } else {
+        (fields.u64(0, "footer")?, fields.u64(0, "footer")?)
+    };
+    if toc_position > start {
+        return Err(EasypackError::InvalidFileError(format!(
+            "The ToC is said to be at {toc_position}, outside of the file"
+        )));
+    }
+    let toc = source
+        .read_at(toc_position, start - toc_position)?
+        .ok_or_else(|| EasypackError::InvalidFileError("Not enough bytes in the ToC".to_owned()))?;
+    Ok((
+        Footer {
+            toc_position,
+            how_many,
+            start,
+            size: footer_size,
+        },
+        toc,
+    ))
+}
+
+/// Parse `toc`, the bytes of a `ToC` of the given `version` with `how_many`
+/// entries.
+/// # Errors
+/// If the version is not supported, or the `ToC` is not valid.
+pub fn parse_toc(toc: &[u8], version: &utils::Version, how_many: u64) -> Result<ParsedToc> {
+    let mut fields = Fields::new(toc);
+    let mut metadata = Metadata::new();
+    let mut entries = Vec::new();
+    match version.clone().into() {
+        (1, 0) => {
+            for i in 0..how_many {
+                let pos = fields.u32(i, "pos")?;
+                let size = fields.u32(i, "size")?;
+                let str_len = fields.u8(i, "str_len")?;
+                let name = fields.take(str_len.into(), i, "name")?.to_vec();
+                entries.push(RawEntry::new(name, pos.into(), size.into(), None));
+            }
+        }
+        (1, v @ (1 | 2)) => {
+            for i in 0..how_many {
+                let pos = fields.u64(i, "pos")?;
+                let size = fields.u64(i, "size")?;
+                let checksum = if v == 2 {
+                    Some(fields.u32(i, "checksum")?)
+                } else {
+                    None
+                };
+                let str_len = fields.u8(i, "str_len")?;
+                let name = fields.take(str_len.into(), i, "name")?.to_vec();
+                entries.push(RawEntry::new(name, pos, size, checksum));
+            }
+        }
+        (1, v @ 3..=7) => {
+            if v >= 6 {
+                metadata = fields.metadata("the archive")?;
+            }
+            for i in 0..how_many {
+                let pos = fields.u64(i, "pos")?;
+                let size = fields.u64(i, "size")?;
+                let checksum = fields.u32(i, "checksum")?;
+                let (compression, uncompressed_size) = if v >= 4 {
+                    (
+                        Compression::from_code(fields.u8(i, "compression")?)?,
+                        fields.u64(i, "uncompressed_size")?,
+                    )
+                } else {
+                    (Compression::None, size)
+                };
+                let record_metadata = if v >= 5 {
+                    fields.metadata(&format!("the {i}th toc"))?
+                } else {
+                    Metadata::new()
+                };
+                let str_len = if v >= 7 {
+                    fields.u32(i, "str_len")?.try_into()?
+                } else {
+                    fields.u8(i, "str_len")?.into()
+                };
+                let name = fields.take(str_len, i, "name")?.to_vec();
+                entries.push(
+                    RawEntry::new(name, pos, size, Some(checksum))
+                        .with_compression(compression, uncompressed_size)
+                        .with_metadata(record_metadata),
+                );
+            }
+        }
+        el => return Err(unsupported(el)),
+    }
+    Ok(ParsedToc {
+        metadata,
+        entries,
+        #[cfg(feature = "std")]
+        len: fields.pos,
+    })
+}
+
+#[cfg(feature = "std")]
+/// Parse only the metadata of the archive out of `toc`, the bytes of a `ToC`
+/// of the given `version`. It is empty for the versions before 1.6, which
+/// have none.
+/// # Errors
+/// If the version is not supported, or the metadata is not valid.
+pub fn parse_archive_metadata(toc: &[u8], version: &utils::Version) -> Result<Metadata> {
+    match version.clone().into() {
+        (1, 0..=5) => Ok(Metadata::new()),
+        (1, 6 | 7) => Fields::new(toc).metadata("the archive"),
+        el => Err(unsupported(el)),
+    }
+}
+
+fn unsupported(version: (u8, u8)) -> EasypackError {
+    EasypackError::InvalidFileError(format!(
+        "Found version `{version:?}`, which is not supported."
+    ))
+}
+
+/// The size of the footer, since version 1.3.
+pub const CHECKED_FOOTER_SIZE: u64 = 28;
+
+// Where the magic number is in the footer, since version 1.3.
+const MAGIC_OFFSET: u64 = 24;
+
+// How many bytes are read at once, when looking back for a valid footer.
+const SCAN_CHUNK_SIZE: u64 = 64 * 1024;
+
+// Read the last valid footer, since version 1.3, and the `ToC` it points to.
+// This is the one at the end of the data, unless it has been left half
+// written: in that case, the footer of the previous update is used.
+fn read_checked_footer<'s>(
+    source: &mut impl Source<'s>,
+    size: u64,
+) -> Result<(Footer, Cow<'s, [u8]>)> {
+    let footer_start = footer_start(size, CHECKED_FOOTER_SIZE)?;
+    if let Some(found) = read_checked_footer_at(source, footer_start)? {
+        return Ok(found);
+    }
+
+    // Look back for the magic number of another footer, from the end of the
+    // data. `magic_end` is where the scan starts, excluded: the footer at the
+    // end of the data has already been checked.
+    let magic = utils::FOOTER_MAGIC.as_bytes();
+    let magic_min = utils::HEADER_SIZE + MAGIC_OFFSET;
+    let mut magic_end = footer_start + MAGIC_OFFSET;
+    while magic_end > magic_min {
+        let magic_start = magic_end.saturating_sub(SCAN_CHUNK_SIZE).max(magic_min);
+        // The last magic number starting in the chunk ends a few bytes after
+        // it.
+        let chunk_len = magic_end - magic_start + u64::try_from(magic.len())? - 1;
+        let chunk = source.read_at(magic_start, chunk_len)?.ok_or_else(|| {
+            EasypackError::InvalidFileError("Not enough bytes in the footer".to_owned())
+        })?;
+        let starts: Vec<u64> = (magic_start..)
+            .zip(chunk.windows(magic.len()))
+            .filter(|(_, window)| *window == magic)
+            .map(|(magic_pos, _)| magic_pos - MAGIC_OFFSET)
+            .collect();
+        // The last footers first.
+        for start in starts.into_iter().rev() {
+            if let Some(found) = read_checked_footer_at(source, start)? {
+                return Ok(found);
+            }
+        }
+        magic_end = magic_start;
+    }
+    Err(EasypackError::InvalidFileError(
+        "No valid footer found".to_owned(),
+    ))
+}
+
+// Read the footer starting at `footer_start`, if it is valid: it must be
+// intact, and point to an intact `ToC`, which ends right before it.
+fn read_checked_footer_at<'s>(
+    source: &mut impl Source<'s>,
+    footer_start: u64,
+) -> Result<Option<(Footer, Cow<'s, [u8]>)>> {
+    let Some(footer) = source.read_at(footer_start, CHECKED_FOOTER_SIZE)? else {
+        return Ok(None);
+    };
+    let mut fields = Fields::new(&footer);
+    let toc_position = fields.u64(0, "footer")?;
+    let how_many = fields.u64(0, "footer")?;
+    let toc_checksum = fields.u32(0, "footer")?;
+    let footer_checksum = fields.u32(0, "footer")?;
+    if footer[24..] != *utils::FOOTER_MAGIC.as_bytes() || footer_checksum != crc32(&footer[..20]) {
+        return Ok(None);
+    }
+    if toc_position < utils::HEADER_SIZE || toc_position > footer_start {
+        return Ok(None);
+    }
+    let Some(toc) = source.read_at(toc_position, footer_start - toc_position)? else {
+        return Ok(None);
+    };
+    if crc32(&toc) != toc_checksum {
+        return Ok(None);
+    }
+    Ok(Some((
+        Footer {
+            toc_position,
+            how_many,
+            start: footer_start,
+            size: CHECKED_FOOTER_SIZE,
+        },
+        toc,
+    )))
+}
+
+// Where a footer of `footer_size` bytes starts, in packed data of `size`
+// bytes.
+fn footer_start(size: u64, footer_size: u64) -> Result<u64> {
+    size.checked_sub(footer_size)
+        .filter(|&start| start >= utils::HEADER_SIZE)
+        .ok_or_else(|| EasypackError::InvalidFileError("Not enough bytes in the footer".to_owned()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    // A 1.1 file with 2 records, built by hand.
+    fn packed_1_1() -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(b"SMPL");
+        data.extend_from_slice(&[1, 1]);
+        data.extend_from_slice(&[0x12, 0x34, 0x56]);
+        for (pos, size, name) in [(6_u64, 2_u64, b"c1"), (8, 1, b"c2")] {
+            data.extend_from_slice(&pos.to_le_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+            data.push(2);
+            data.extend_from_slice(name);
+        }
+        data.extend_from_slice(&9_u64.to_le_bytes());
+        data.extend_from_slice(&2_u64.to_le_bytes());
+        data
+    }
+
+    #[test]
+    /// The `ToC` of a 1.0 file can be read.
+    fn slice_read_toc_1_0() -> Result<()> {
+        let mut data = vec![];
+        data.extend_from_slice(b"SMPL");
+        data.extend_from_slice(&[1, 0]);
+        data.extend_from_slice(&[0x12, 0x34]);
+        data.extend_from_slice(&6_u32.to_le_bytes());
+        data.extend_from_slice(&2_u32.to_le_bytes());
+        data.push(2);
+        data.extend_from_slice(b"c1");
+        data.extend_from_slice(&8_u32.to_le_bytes());
+        data.extend_from_slice(&1_u32.to_le_bytes());
+
+        let toc = read_toc(&data)?;
+        assert_eq!(toc.len(), 1);
+        let entry = toc
+            .get("c1")
+            .ok_or_else(|| EasypackError::InternalError("c1".to_owned()))?;
+        assert_eq!((entry.data_start, entry.data_len), (6, 2));
+        assert_eq!(super::super::entry_slice(&data, entry)?, [0x12, 0x34]);
+        Ok(())
+    }
+
+    #[test]
+    /// The `ToC` of a 1.1 file can be read.
+    fn slice_read_toc_1_1() -> Result<()> {
+        let data = packed_1_1();
+        let toc = read_toc(&data)?;
+        assert_eq!(toc.len(), 2);
+        let entry = toc
+            .get("c2")
+            .ok_or_else(|| EasypackError::InternalError("c2".to_owned()))?;
+        assert_eq!(super::super::entry_slice(&data, entry)?, [0x56]);
+        assert!(toc.get("nope").is_none());
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    // An archive written with the format `F`, with all that it can store: what
+    // it can't is left out.
+    fn pack_fixture<F: crate::writers::packer::PackFormat>(
+    ) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+        use crate::writers::packer::{NoneStep, Packer};
+
+        let mut writer =
+            Packer::<NoneStep, _, F>::from_writer(std::io::Cursor::new(vec![])).write_header()?;
+        if F::ARCHIVE_METADATA {
+            writer.set_metadata(
+                Metadata::new().with_value("tool", MetadataValue::Text("easypack".to_owned())),
+            )?;
+        }
+        writer.write_record(crate::Record::new("c1".to_owned(), vec![0x12, 0x34]))?;
+        writer.write_compressed_record(
+            crate::Record::new("text".to_owned(), TEXT.repeat(10)),
+            if F::COMPRESSION {
+                Compression::Lz
+            } else {
+                Compression::None
+            },
+        )?;
+        let mut metadata = Metadata::new();
+        if F::RECORD_METADATA {
+            metadata = metadata.with_mode(0o644);
+        }
+        writer.write_record(
+            crate::Record::new("c2".to_owned(), vec![0x56]).with_metadata(metadata),
+        )?;
+        let name = "x".repeat(F::MAX_NAME_LEN.min(300).try_into()?);
+        writer.write_record(crate::Record::new(name, vec![0x78]))?;
+        let (buff, _) = writer.close()?;
+        Ok(buff.into_inner())
+    }
+
+    #[cfg(feature = "std")]
+    // The data of the record named "text" in `pack_fixture`, repeated.
+    const TEXT: &[u8] = b"Some text, which is worth compressing. ";
+
+    #[cfg(feature = "std")]
+    #[test]
+    /// The `ToC` of an archive written by the default `Packer` can be read.
+    fn slice_read_toc_default_packer() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed = pack_fixture::<crate::writers::Format>()?;
+        let toc = read_toc(&packed)?;
+        assert_eq!(toc.len(), 4);
+        assert_eq!(
+            toc.metadata().get("tool"),
+            Some(&MetadataValue::Text("easypack".to_owned()))
+        );
+        let get = |name: &str| {
+            toc.get(name)
+                .ok_or_else(|| EasypackError::InternalError(name.to_owned()))
+        };
+        let data = |name| super::super::entry_data(&packed, get(name)?);
+        assert_eq!(*data("c1")?, [0x12, 0x34]);
+        assert_eq!(get("text")?.compression, Compression::Lz);
+        assert_eq!(*data("text")?, *TEXT.repeat(10));
+        assert_eq!(get("c2")?.metadata.mode(), Some(0o644));
+        assert_eq!(*data("c2")?, [0x56]);
+        assert_eq!(*data(&"x".repeat(300))?, [0x78]);
+
+        // After an interrupted update, the previous footer is used.
+        let mut data = packed.clone();
+        data.extend_from_slice(&[0x9a; 100]);
+        assert_eq!(read_toc(&data)?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    /// The `ToC` of a 1.7 file can be read, with or without `std`, and an
+    /// interrupted update falls back on the previous footer.
+    fn slice_read_toc_1_7() -> Result<()> {
+        let mut data = vec![];
+        data.extend_from_slice(b"SMPL");
+        data.extend_from_slice(&[1, 7]);
+        data.extend_from_slice(&[0x12, 0x34]);
+        let toc_position = 8_u64;
+        let mut toc = vec![];
+        toc.extend_from_slice(&1_u16.to_le_bytes());
+        toc.push(4);
+        toc.extend_from_slice(b"tool");
+        toc.push(1);
+        toc.extend_from_slice(&8_u32.to_le_bytes());
+        toc.extend_from_slice(b"easypack");
+        toc.extend_from_slice(&6_u64.to_le_bytes());
+        toc.extend_from_slice(&2_u64.to_le_bytes());
+        toc.extend_from_slice(&crc32(&[0x12, 0x34]).to_le_bytes());
+        toc.push(Compression::None.code());
+        toc.extend_from_slice(&2_u64.to_le_bytes());
+        toc.extend_from_slice(&0_u16.to_le_bytes());
+        toc.extend_from_slice(&300_u32.to_le_bytes());
+        toc.extend_from_slice(&[b'x'; 300]);
+        data.extend_from_slice(&toc);
+        let mut footer = vec![];
+        footer.extend_from_slice(&toc_position.to_le_bytes());
+        footer.extend_from_slice(&1_u64.to_le_bytes());
+        footer.extend_from_slice(&crc32(&toc).to_le_bytes());
+        footer.extend_from_slice(&crc32(&footer).to_le_bytes());
+        footer.extend_from_slice(utils::FOOTER_MAGIC.as_bytes());
+        data.extend_from_slice(&footer);
+        // An update which was interrupted, after writing half of its footer.
+        data.extend_from_slice(&[0x9a; 100]);
+        data.extend_from_slice(&footer[..20]);
+
+        let toc = read_toc(&data)?;
+        assert_eq!(toc.len(), 1);
+        assert_eq!(
+            toc.metadata().get("tool"),
+            Some(&MetadataValue::Text("easypack".to_owned()))
+        );
+        let entry = toc
+            .get(&"x".repeat(300))
+            .ok_or_else(|| EasypackError::InternalError("x".to_owned()))?;
+        assert_eq!(*super::super::entry_data(&data, entry)?, [0x12, 0x34]);
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    /// The `ToC` is read as the other readers do, for all the versions since
+    /// 1.3.
+    fn slice_read_toc_checked_versions() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::writers::{ver_1_3, ver_1_4, ver_1_5, ver_1_6, ver_1_7};
+
+        for data in [
+            pack_fixture::<ver_1_3::Format>()?,
+            pack_fixture::<ver_1_4::Format>()?,
+            pack_fixture::<ver_1_5::Format>()?,
+            pack_fixture::<ver_1_6::Format>()?,
+            pack_fixture::<ver_1_7::Format>()?,
+        ] {
+            let toc = read_toc(&data)?;
+            let archive = crate::Archive::from_reader(std::io::Cursor::new(data.clone()))?;
+            assert_eq!(
+                toc.iter().collect::<Vec<_>>(),
+                archive.entries().collect::<Vec<_>>()
+            );
+            assert_eq!(toc.metadata(), archive.metadata());
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Broken data is an error, not a panic.
+    fn slice_read_toc_invalid() {
+        assert!(read_toc(b"This is just text.").is_err());
+        assert!(read_toc(b"SMPL").is_err());
+        assert!(read_toc(b"SMPL\x01\x07").is_err());
+
+        // A 1.1 file has no checksums, so a truncated one may look valid by
+        // chance, but never with the original `ToC`.
+        let data = packed_1_1();
+        for len in 0..data.len() {
+            assert!(!read_toc(&data[..len]).is_ok_and(|toc| toc.len() == 2));
+        }
+        // The `ToC` is said to be after the end of the data.
+        let mut data = packed_1_1();
+        let len = data.len();
+        data[len - 16..len - 8].copy_from_slice(&1000_u64.to_le_bytes());
+        assert!(read_toc(&data).is_err());
+    }
+}
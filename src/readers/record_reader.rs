@@ -0,0 +1,293 @@
+//! A reader over a single record.
+//! It is used to stream the content of a record, instead of loading all of it
+//! in memory at once.
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use crate::checksum::Crc32;
+use crate::compression::Compression;
+use crate::error::{EasypackError, Result};
+
+use super::Entry;
+
+/// Anything we can read from and seek into.
+/// This is needed since a trait object can only have a single (non-auto)
+/// trait.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/**
+A reader over the data of a single record.
+
+The reader can't go outside of the record: reads stop at the end of the
+record, and positions are relative to the record start. This means that
+`SeekFrom::Start(0)` is the first byte of the record, and `SeekFrom::End(0)`
+is just after its last byte.
+
+If the record has a checksum, and it is read from the start to the end without
+seeking around, the data is verified against it: the read reaching the end of
+the record fails with `std::io::ErrorKind::InvalidData` on a mismatch, with an
+`EasypackError::ChecksumMismatch` inside.
+
+Compressed records can't be streamed: they are read and decompressed as a
+whole when the reader is created, and then read from memory.
+*/
+pub struct RecordReader<'a> {
+    reader: &'a mut dyn ReadSeek,
+    // Where the record starts in the underlying reader.
+    data_start: u64,
+    data_len: u64,
+    // The position, relative to `data_start`.
+    pos: u64,
+    // The name of the record, for the errors.
+    name: String,
+    // The checksum computed so far, and the expected one. Only there while
+    // the record is read sequentially.
+    checksum: Option<(Crc32, u32)>,
+    // The data of a compressed record, already decompressed.
+    decompressed: Option<Cursor<Vec<u8>>>,
+}
+
+impl<'a> RecordReader<'a> {
+    /// Create a reader over the record described by `entry`.
+    /// # Errors
+    /// If we are unable to seek to the start of the record. If the record is
+    /// compressed, any error reading and decompressing it.
+    pub fn new(reader: &'a mut dyn ReadSeek, entry: &Entry) -> Result<Self> {
+        let decompressed = if entry.compression == Compression::None {
+            reader.seek(SeekFrom::Start(entry.data_start))?;
+            None
+        } else {
+            Some(Cursor::new(super::read_entry(reader, entry)?))
+        };
+        Ok(Self {
+            reader,
+            data_start: entry.data_start,
+            data_len: entry.uncompressed_len,
+            pos: 0,
+            name: entry.name.clone(),
+            checksum: entry.checksum.map(|checksum| (Crc32::new(), checksum)),
+            decompressed,
+        })
+    }
+
+    #[must_use]
+    /// The size of the record.
+    pub const fn len(&self) -> u64 {
+        self.data_len
+    }
+
+    #[must_use]
+    /// Whether the record has no data at all.
+    pub const fn is_empty(&self) -> bool {
+        self.data_len == 0
+    }
+}
+
+impl Read for RecordReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(decompressed) = self.decompressed.as_mut() {
+            // Already verified against the checksum.
+            return decompressed.read(buf);
+        }
+        let remaining = self.data_len.saturating_sub(self.pos);
+        // If the remaining data does not fit in usize, then the buffer is the
+        // limit anyway.
+        let to_read = usize::try_from(remaining).map_or(buf.len(), |r| r.min(buf.len()));
+        if to_read == 0 {
+            self.verify_checksum()?;
+            return Ok(0);
+        }
+        let bytes_read = self.reader.read(&mut buf[..to_read])?;
+        if bytes_read == 0 {
+            // The data ends before the record does: the file is truncated.
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                EasypackError::InvalidFileError(format!(
+                    "Not enough bytes to read record {}",
+                    self.name
+                )),
+            ));
+        }
+        self.pos += bytes_read as u64;
+        if let Some((crc, _)) = self.checksum.as_mut() {
+            crc.update(&buf[..bytes_read]);
+        }
+        if self.pos == self.data_len {
+            self.verify_checksum()?;
+        }
+        Ok(bytes_read)
+    }
+}
+
+impl RecordReader<'_> {
+    // Once all the data is read, check the checksum (only once).
+    fn verify_checksum(&mut self) -> std::io::Result<()> {
+        if let Some((crc, expected)) = self.checksum.take() {
+            let actual = crc.finish();
+            if actual != expected {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    EasypackError::ChecksumMismatch(format!(
+                        "Record {} has checksum {actual:#010x}, expected {expected:#010x}",
+                        self.name
+                    )),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Seek for RecordReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        if let Some(decompressed) = self.decompressed.as_mut() {
+            return decompressed.seek(pos);
+        }
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.data_len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        let new_pos = new_pos.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        // Going past the end is fine, as for files: reads will just return no
+        // data. But the underlying reader is never moved outside the record.
+        let start = self
+            .data_start
+            .checked_add(new_pos.min(self.data_len))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    EasypackError::InvalidFileError(format!(
+                        "Record {} starts at {}, unable to seek to {new_pos}",
+                        self.name, self.data_start
+                    )),
+                )
+            })?;
+        self.reader.seek(SeekFrom::Start(start))?;
+        if new_pos != self.pos {
+            // The data is not read sequentially anymore, so there is no way
+            // to compute the checksum.
+            self.checksum = None;
+        }
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, RecordReader};
+    use crate::checksum::crc32;
+    use crate::readers::get_unpacker;
+    use crate::utils::Record;
+    use crate::writers::ver_1_1::Packer;
+
+    use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom};
+
+    fn packed() -> crate::error::Result<Cursor<Vec<u8>>> {
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(Record::new("first".to_owned(), vec![0x01, 0x02]))?;
+            writer.write_record(Record::new(
+                "second".to_owned(),
+                vec![0x10, 0x11, 0x12, 0x13, 0x14, 0x15],
+            ))?;
+            writer.write_record(Record::new("third".to_owned(), vec![0x20, 0x21]))?;
+            writer.close()?;
+        }
+        Ok(buff)
+    }
+
+    #[test]
+    /// The reader gives back exactly the record, and nothing more.
+    fn read_whole_record() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut unpacker = get_unpacker(packed()?)?;
+        unpacker.init()?;
+        assert!(unpacker.open_record("nope")?.is_none());
+
+        let mut reader = unpacker.open_record("second")?.unwrap();
+        assert_eq!(reader.len(), 6);
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        assert_eq!(data, vec![0x10, 0x11, 0x12, 0x13, 0x14, 0x15]);
+        // Nothing more to read.
+        assert_eq!(reader.read(&mut [0; 4])?, 0);
+        Ok(())
+    }
+
+    #[test]
+    /// Positions are relative to the record, and can't go outside of it.
+    fn seek_in_record() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut unpacker = get_unpacker(packed()?)?;
+        unpacker.init()?;
+        let mut reader = unpacker.open_record("second")?.unwrap();
+
+        let mut buf = [0; 2];
+        assert_eq!(reader.seek(SeekFrom::Start(2))?, 2);
+        reader.read_exact(&mut buf)?;
+        assert_eq!(buf, [0x12, 0x13]);
+
+        assert_eq!(reader.seek(SeekFrom::Current(-3))?, 1);
+        reader.read_exact(&mut buf)?;
+        assert_eq!(buf, [0x11, 0x12]);
+
+        assert_eq!(reader.seek(SeekFrom::End(-1))?, 5);
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        assert_eq!(data, vec![0x15]);
+
+        // Past the end there is nothing to read, not even the next record.
+        assert_eq!(reader.seek(SeekFrom::End(3))?, 9);
+        assert_eq!(reader.read(&mut buf)?, 0);
+
+        // Before the start is not allowed.
+        assert!(reader.seek(SeekFrom::Current(-10)).is_err());
+        assert!(reader.seek(SeekFrom::End(-7)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// A record cut short by the end of the data is an error, not a short
+    /// record.
+    fn truncated_record() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let data: Vec<u8> = (0..37).collect();
+        let entry = Entry::new("c1".to_owned(), 0, 1000, Some(crc32(&data)));
+        let mut buff = Cursor::new(data);
+        let mut reader = RecordReader::new(&mut buff, &entry)?;
+        let err = reader.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // The same without a checksum.
+        let entry = Entry::new("c1".to_owned(), 0, 1000, None);
+        let mut reader = RecordReader::new(&mut buff, &entry)?;
+        let mut data = vec![0; 1000];
+        assert!(reader.read_exact(&mut data).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Empty records give an empty reader.
+    fn empty_record() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(Record::new("empty".to_owned(), vec![]))?;
+            writer.close()?;
+        }
+        let mut unpacker = get_unpacker(buff)?;
+        unpacker.init()?;
+        let mut reader = unpacker.open_record("empty")?.unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(reader.read(&mut [0; 4])?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+//! The `ToC` (table of contents) of a packed file, once it is read.
+//! Every version stores it differently in the file, but once read it is the
+//! same for all of them.
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+// Without `std` there are no hash maps, a tree is the next best thing.
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+
+use crate::compression::Compression;
+use crate::error::{EasypackError, Result};
+use crate::metadata::Metadata;
+
+/// A single record, as described in the `ToC`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The name of the record.
+    pub name: String,
+    /// Where the data of the record starts in the file.
+    pub data_start: u64,
+    /// The size of the data of the record.
+    pub data_len: u64,
+    /// The CRC-32 of the data, for the versions that store it.
+    pub checksum: Option<u32>,
+    /// How the data is compressed, since version 1.4.
+    pub compression: Compression,
+    /// The size of the data once decompressed: the same as `data_len`, unless
+    /// the data is compressed.
+    pub uncompressed_len: u64,
+    /// The metadata of the record, since version 1.5.
+    pub metadata: Metadata,
+}
+
+impl Entry {
+    #[must_use]
+    pub const fn new(name: String, data_start: u64, data_len: u64, checksum: Option<u32>) -> Self {
+        Self {
+            name,
+            data_start,
+            data_len,
+            checksum,
+            compression: Compression::None,
+            uncompressed_len: data_len,
+            metadata: Metadata::new(),
+        }
+    }
+
+    #[must_use]
+    /// The same entry, with its data compressed.
+    pub const fn with_compression(
+        mut self,
+        compression: Compression,
+        uncompressed_len: u64,
+    ) -> Self {
+        self.compression = compression;
+        self.uncompressed_len = uncompressed_len;
+        self
+    }
+
+    #[must_use]
+    /// The same entry, with the given `metadata`.
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+/// A single record, as read from the `ToC`, before checking that its name is
+/// valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawEntry {
+    pub name: Vec<u8>,
+    pub data_start: u64,
+    pub data_len: u64,
+    pub checksum: Option<u32>,
+    pub compression: Compression,
+    pub uncompressed_len: u64,
+    pub metadata: Metadata,
+}
+
+impl RawEntry {
+    #[must_use]
+    pub const fn new(name: Vec<u8>, data_start: u64, data_len: u64, checksum: Option<u32>) -> Self {
+        Self {
+            name,
+            data_start,
+            data_len,
+            checksum,
+            compression: Compression::None,
+            uncompressed_len: data_len,
+            metadata: Metadata::new(),
+        }
+    }
+
+    #[must_use]
+    /// The same entry, with its data compressed.
+    pub const fn with_compression(
+        mut self,
+        compression: Compression,
+        uncompressed_len: u64,
+    ) -> Self {
+        self.compression = compression;
+        self.uncompressed_len = uncompressed_len;
+        self
+    }
+
+    #[must_use]
+    /// The same entry, with the given `metadata`.
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+impl TryFrom<RawEntry> for Entry {
+    type Error = EasypackError;
+
+    fn try_from(raw: RawEntry) -> Result<Self> {
+        let name = String::from_utf8(raw.name).map_err(|e| {
+            EasypackError::InvalidFileError(format!("A record name is not valid utf8: {e}"))
+        })?;
+        Ok(Self::new(name, raw.data_start, raw.data_len, raw.checksum)
+            .with_compression(raw.compression, raw.uncompressed_len)
+            .with_metadata(raw.metadata))
+    }
+}
+
+/// The `ToC`, indexed by record name so that lookups don't need to scan it.
+#[derive(Debug, Default)]
+pub struct Toc {
+    entries: Vec<Entry>,
+    // Where to find each record in `entries`, by name.
+    index: Map<String, usize>,
+    // The metadata of the archive, since version 1.6.
+    metadata: Metadata,
+}
+
+impl Toc {
+    #[must_use]
+    /// Build the `ToC` from its entries, in the order they are stored.
+    /// If a name is there more than once, the first one wins, as it was when
+    /// the table was scanned linearly.
+    pub fn new(entries: Vec<Entry>) -> Self {
+        let mut index = Map::new();
+        for (i, entry) in entries.iter().enumerate() {
+            index.entry(entry.name.clone()).or_insert(i);
+        }
+        Self {
+            entries,
+            index,
+            metadata: Metadata::new(),
+        }
+    }
+
+    #[must_use]
+    /// The same `ToC`, with the given metadata of the archive.
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Build the `ToC` from the entries read from the file.
+    /// # Errors
+    /// If any name is not valid utf8.
+    pub fn from_raw(entries: Vec<RawEntry>) -> Result<Self> {
+        Ok(Self::new(
+            entries
+                .into_iter()
+                .map(Entry::try_from)
+                .collect::<Result<_>>()?,
+        ))
+    }
+
+    #[must_use]
+    /// The entry of the record named `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.index.get(name).and_then(|&i| self.entries.get(i))
+    }
+
+    #[cfg(feature = "mmap")]
+    #[must_use]
+    /// The position in the `ToC` of the record named `name`, and its entry, if
+    /// any.
+    pub fn find(&self, name: &str) -> Option<(usize, &Entry)> {
+        self.index
+            .get(name)
+            .and_then(|&i| Some((i, self.entries.get(i)?)))
+    }
+
+    #[must_use]
+    /// Whether there is a record named `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    #[must_use]
+    /// The number of entries.
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// All the entries, in the order they are stored.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    #[must_use]
+    /// The metadata of the archive.
+    pub const fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Lookups go through the index, without scanning the entries: they
+    /// don't get slower as the `ToC` gets bigger.
+    fn lookups_use_index() {
+        let entries = (0..100_000)
+            .map(|i| Entry::new(format!("record_{i}"), i, 1, None))
+            .collect();
+        let mut toc = Toc::new(entries);
+        assert_eq!(toc.index.len(), 100_000);
+        // Renaming an entry behind the back of the index does not change
+        // what it finds, which a scan would.
+        toc.entries[99_999].name = "renamed".to_owned();
+        assert_eq!(toc.get("record_99999").map(|e| e.data_start), Some(99_999));
+        assert!(toc.get("renamed").is_none());
+        assert!(toc.contains("record_99999"));
+        assert_eq!(toc.get("record_42").map(|e| e.data_start), Some(42));
+    }
+
+    #[test]
+    /// If a name is there more than once, the first one wins.
+    fn duplicate_names() {
+        let toc = Toc::new(vec![
+            Entry::new("c1".to_owned(), 6, 1, None),
+            Entry::new("c1".to_owned(), 7, 1, None),
+        ]);
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc.get("c1").map(|e| e.data_start), Some(6));
+    }
+}
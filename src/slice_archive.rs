@@ -0,0 +1,101 @@
+//! Packed data already in memory, e.g. embedded with `include_bytes!`.
+//! This does not need `std`, so it can be used to read packed data stored in
+//! flash on embedded targets.
+use alloc::borrow::Cow;
+
+use crate::error::Result;
+use crate::metadata::Metadata;
+use crate::readers::{self, Entry, Toc};
+
+/**
+Packed data already in memory, whatever its (supported) version is.
+
+It is created by `Archive::from_bytes`, or `SliceArchive::new` without the
+`std` feature. The `ToC` is read once, and every record is a slice borrowed
+from the data: nothing is ever copied, unless the record is compressed.
+*/
+#[cfg_attr(
+    feature = "std",
+    doc = r#"
+# Usage.
+
+```
+use easypack::*;
+
+# let packed_file = std::path::PathBuf::from("/tmp/__slice_archive_doc__.bin");
+# pack_records(&packed_file, [Record::new("c1".into(), vec![0x12, 0x34])].into_iter()).unwrap();
+# let data = std::fs::read(&packed_file).unwrap();
+# std::fs::remove_file(&packed_file).unwrap();
+// E.g. `static DATA: &[u8] = include_bytes!("data.bin");`
+let archive = Archive::from_bytes(&data).unwrap();
+assert_eq!(archive.get("c1").unwrap().as_deref(), Some([0x12, 0x34].as_slice()));
+assert!(archive.get("nope").unwrap().is_none());
+```
+"#
+)]
+pub struct SliceArchive<'a> {
+    data: &'a [u8],
+    toc: Toc,
+}
+
+impl<'a> SliceArchive<'a> {
+    /// Use `data` as the packed data, and read its `ToC`.
+    /// # Errors
+    /// If the data is not valid packed data.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let toc = readers::parser::read_toc(data)?;
+        Ok(Self { data, toc })
+    }
+
+    /// The data of the record associated with `name`, if any.
+    /// It is borrowed from the data, unless the record is compressed.
+    /// # Errors
+    /// If the record is not inside the data, it does not match its checksum,
+    /// or it can't be decompressed.
+    pub fn get(&self, name: &str) -> Result<Option<Cow<'a, [u8]>>> {
+        self.toc
+            .get(name)
+            .map(|entry| readers::entry_data(self.data, entry))
+            .transpose()
+    }
+
+    #[must_use]
+    /// The entry of the record associated with `name`, if any.
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.toc.get(name)
+    }
+
+    #[must_use]
+    /// Whether there is a record associated with `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.toc.contains(name)
+    }
+
+    #[must_use]
+    /// The number of records.
+    pub const fn len(&self) -> usize {
+        self.toc.len()
+    }
+
+    #[must_use]
+    /// Whether there are no records at all.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The names of all the records, in the order they are stored.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.toc.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// The entries of all the records, in the order they are stored.
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.toc.iter()
+    }
+
+    #[must_use]
+    /// The metadata of the archive, empty for the versions before 1.6.
+    pub const fn metadata(&self) -> &Metadata {
+        self.toc.metadata()
+    }
+}
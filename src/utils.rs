@@ -1,9 +1,27 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::metadata::Metadata;
+#[cfg(feature = "std")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "std")]
+use crate::error::Result;
+#[cfg(feature = "std")]
+use crate::OpenMode;
+
 /// The file header.
 pub static FILE_TYPE: &str = "SMPL";
+/// The magic number at the end of the footer, since version 1.3.
+pub static FOOTER_MAGIC: &str = "SMPF";
 /// The header size.
 pub static HEADER_SIZE: u64 = 6;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Version {
     maj: u8,
     min: u8,
@@ -26,17 +44,222 @@ impl From<Version> for (u8, u8) {
 pub struct Record {
     pub name: String,
     pub data: Vec<u8>,
+    /// The metadata, since version 1.5.
+    pub metadata: Metadata,
 }
 
 impl Record {
     #[must_use]
-    /// Create a new record.
-    pub fn new(name: String, data: Vec<u8>) -> Self {
-        Self { name, data }
+    /// Create a new record, without metadata.
+    pub const fn new(name: String, data: Vec<u8>) -> Self {
+        Self {
+            name,
+            data,
+            metadata: Metadata::new(),
+        }
+    }
+
+    #[must_use]
+    /// The same record, with the given `metadata`.
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+/**
+A file written in place of `dest`, without touching `dest` until it is
+complete.
+
+The data goes to a temporary file next to `dest`, which is moved over it by
+`persist`. If `persist` is never called, e.g. because of an error while
+writing, the temporary file is removed when dropped, so that nobody can ever
+see a half written file at `dest`.
+Whatever was at `dest` is replaced as a whole, unless the `OpenMode` says that
+`dest` must not exist.
+
+When `dest` is a symlink, the file it points to is replaced, and the link is
+kept. The new file gets the permissions of the file it replaces, and its owner
+and group when they can be set (only root can give a file to another user).
+*/
+pub struct AtomicFile {
+    dest: PathBuf,
+    tmp_path: PathBuf,
+    file: File,
+    mode: OpenMode,
+    persisted: bool,
+}
+
+#[cfg(feature = "std")]
+impl AtomicFile {
+    /// Create the temporary file for `dest`.
+    /// # Errors
+    /// Any IO error. If `dest` already exists and `mode` is
+    /// `OpenMode::CreateNew`, an `AlreadyExists` one.
+    pub fn create(dest: impl AsRef<Path>, mode: OpenMode) -> Result<Self> {
+        let mut dest = dest.as_ref().to_path_buf();
+        // Fail early, rather than after writing all the data. This does not
+        // follow symlinks, as `persist` does not either.
+        if mode == OpenMode::CreateNew && dest.symlink_metadata().is_ok() {
+            return Err(already_exists(&dest).into());
+        }
+        if mode == OpenMode::Truncate {
+            dest = resolve_symlinks(dest)?;
+        }
+        let tmp_path = Self::tmp_path(&dest);
+        let file = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&tmp_path)?;
+        if mode == OpenMode::Truncate {
+            if let Ok(metadata) = std::fs::metadata(&dest) {
+                file.set_permissions(metadata.permissions())?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::MetadataExt;
+                    // Best effort, see the documentation of the struct.
+                    let _ = std::os::unix::fs::fchown(
+                        &file,
+                        Some(metadata.uid()),
+                        Some(metadata.gid()),
+                    );
+                }
+            }
+        }
+        Ok(Self {
+            dest,
+            tmp_path,
+            file,
+            mode,
+            persisted: false,
+        })
+    }
+
+    // A unique path in the same directory as `dest`, so that it can be
+    // renamed over it.
+    fn tmp_path(dest: &Path) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut file_name = std::ffi::OsString::from(".");
+        file_name.push(dest.file_name().unwrap_or_default());
+        file_name.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        dest.with_file_name(file_name)
+    }
+
+    #[must_use]
+    /// The temporary file, to write the data to.
+    pub const fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Flush the data to disk, and move the file to `dest`.
+    /// # Errors
+    /// Any IO error. The temporary file is removed, and `dest` is untouched.
+    pub fn persist(mut self) -> Result<()> {
+        self.file.sync_all()?;
+        match self.mode {
+            OpenMode::Truncate => std::fs::rename(&self.tmp_path, &self.dest)?,
+            // A rename would replace `dest` if it has been created in the
+            // meantime, while a link fails.
+            OpenMode::CreateNew => {
+                match std::fs::hard_link(&self.tmp_path, &self.dest) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                        return Err(already_exists(&self.dest).into());
+                    }
+                    // Not every file system has hard links: copy the data
+                    // instead, to a file which must not exist either.
+                    Err(_) => copy_new(&self.tmp_path, &self.dest)?,
+                }
+                // Best effort, `dest` is complete anyway.
+                let _ = std::fs::remove_file(&self.tmp_path);
+            }
+        }
+        self.persisted = true;
+        // Make the rename itself durable, when possible. `dest` is complete
+        // anyway, so a failure here is not an error.
+        #[cfg(unix)]
+        if let Some(parent) = self.dest.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            let _ = File::open(parent).and_then(|parent| parent.sync_all());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+// Follow `path` while it is a symlink, to the file it points to in the end,
+// which may not exist yet.
+fn resolve_symlinks(mut path: PathBuf) -> std::io::Result<PathBuf> {
+    // As many as Linux follows, before giving up with `ELOOP`.
+    for _ in 0..40 {
+        match path.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = std::fs::read_link(&path)?;
+                path = path.parent().unwrap_or_else(|| Path::new("")).join(target);
+            }
+            _ => return Ok(path),
+        }
+    }
+    Err(std::io::Error::other(format!(
+        "Too many levels of symlinks at {}",
+        path.display()
+    )))
+}
+
+#[cfg(feature = "std")]
+/// Copy `from` to the new file `dest`, failing if `dest` already exists.
+/// Nothing is left at `dest` on errors.
+/// # Errors
+/// Any IO error, an `AlreadyExists` one if `dest` already exists.
+pub fn copy_new(from: &Path, dest: &Path) -> std::io::Result<()> {
+    let mut from = File::open(from)?;
+    let mut out = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(dest)
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::AlreadyExists {
+                already_exists(dest)
+            } else {
+                e
+            }
+        })?;
+    let res = std::io::copy(&mut from, &mut out).and_then(|_| out.sync_all());
+    if res.is_err() {
+        // Best effort, the copy failed anyway.
+        let _ = std::fs::remove_file(dest);
+    }
+    res
+}
+
+#[cfg(feature = "std")]
+fn already_exists(dest: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!("{} already exists", dest.display()),
+    )
+}
+
+#[cfg(feature = "std")]
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            // Best effort, there is nothing to do if it fails.
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 pub mod test {
     use std::fs;
     use std::ops::Deref;
@@ -49,7 +272,8 @@ pub mod test {
     impl Tempfile {
         pub fn from_path(path: PathBuf) -> Self {
             if path.exists() {
-                fs::remove_file(&path).expect("Unable to remove {path}");
+                fs::remove_file(&path)
+                    .unwrap_or_else(|e| panic!("Unable to remove {}: {e}", path.display()));
             }
             Self { path }
         }
@@ -65,8 +289,9 @@ pub mod test {
     impl Drop for Tempfile {
         fn drop(&mut self) {
             if self.path.exists() && self.path.is_file() {
-                fs::remove_file(&self.path)
-                    .unwrap_or_else(|e| eprintln!("Unable to remove `{:?}`: {}", &self.path, e));
+                fs::remove_file(&self.path).unwrap_or_else(|e| {
+                    eprintln!("Unable to remove `{}`: {}", self.path.display(), e);
+                });
             }
         }
     }
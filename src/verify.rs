@@ -0,0 +1,396 @@
+//! Check that a packed file is sound, without unpacking it.
+//! The header, the footer and the `ToC` are read as they are, and every
+//! problem found is reported, instead of stopping at the first one.
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::checksum::Crc32;
+use crate::error::Result;
+use crate::readers::{self, parser, IoSource, RawEntry};
+use crate::utils;
+
+/// A problem found in a packed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The header, the footer or the `ToC` can't be read at all, so nothing
+    /// else can be checked.
+    Unreadable(String),
+    /// The `ToC` does not end where the footer starts.
+    TocMisplaced { toc_end: u64, footer_start: u64 },
+    /// There are bytes after the last valid footer, probably left by an
+    /// update which did not complete. The file can still be read as it was
+    /// before the update.
+    IncompleteUpdate { valid_len: u64, file_len: u64 },
+    /// The name of the `index`th record is not valid utf8.
+    InvalidName { index: u64, name: String },
+    /// The same name is used by more than one record.
+    DuplicateName(String),
+    /// The data of a record is not all inside the file, after the header.
+    OutOfBounds {
+        name: String,
+        data_start: u64,
+        data_len: u64,
+    },
+    /// The data of a record overlaps the `ToC` or the footer.
+    OverlapsToc {
+        name: String,
+        data_start: u64,
+        data_len: u64,
+    },
+    /// The data of a record does not match its checksum.
+    ChecksumMismatch {
+        name: String,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreadable(reason) => write!(f, "unreadable file: {reason}"),
+            Self::TocMisplaced {
+                toc_end,
+                footer_start,
+            } => write!(
+                f,
+                "the ToC ends at {toc_end}, but the footer starts at {footer_start}"
+            ),
+            Self::IncompleteUpdate {
+                valid_len,
+                file_len,
+            } => write!(
+                f,
+                "the file is {file_len} bytes long, but only the first {valid_len} are valid"
+            ),
+            Self::InvalidName { index, name } => {
+                write!(f, "the name of record {index} is not valid utf8: {name}")
+            }
+            Self::DuplicateName(name) => write!(f, "record {name} is there more than once"),
+            Self::OutOfBounds {
+                name,
+                data_start,
+                data_len,
+            } => write!(
+                f,
+                "record {name} ({data_len} bytes at {data_start}) is outside of the file"
+            ),
+            Self::OverlapsToc {
+                name,
+                data_start,
+                data_len,
+            } => write!(
+                f,
+                "record {name} ({data_len} bytes at {data_start}) overlaps the ToC"
+            ),
+            Self::ChecksumMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "record {name} has checksum {actual:#010x}, expected {expected:#010x}"
+            ),
+        }
+    }
+}
+
+/// Verify the packed file `infile`.
+///
+/// Check that its header, footer and `ToC` can be read, that every record
+/// lies inside the file without overlapping the `ToC` or the footer, that
+/// names are unique and valid utf8, and that the data matches the checksums
+/// (for the versions that have them).
+///
+/// # Returns
+///
+/// All the problems found, which is empty if the file is sound.
+///
+/// # Errors
+///
+/// Any IO error while reading the file. A file which is not a packed file is
+/// not an error, but a `Problem`.
+pub fn verify(infile: impl AsRef<Path>) -> Result<Vec<Problem>> {
+    let infile = OpenOptions::new().create(false).read(true).open(&infile)?;
+    verify_reader(&mut BufReader::new(infile))
+}
+
+/// Verify the packed data in `r`, see `verify`.
+pub fn verify_reader<R: Read + Seek>(r: &mut R) -> Result<Vec<Problem>> {
+    let file_len = r.seek(SeekFrom::End(0))?;
+
+    let version = match readers::read_header(r) {
+        Ok(version) => version,
+        Err(e) => return Ok(vec![Problem::Unreadable(format!("header: {e}"))]),
+    };
+    if file_len < utils::HEADER_SIZE {
+        return Ok(vec![Problem::Unreadable(
+            "the file is too short".to_owned(),
+        )]);
+    }
+    let (footer, toc) = match parser::read_footer(&mut IoSource(&mut *r), &version) {
+        Ok(found) => found,
+        Err(e) => return Ok(vec![Problem::Unreadable(format!("footer: {e}"))]),
+    };
+    let footer_start = footer.start;
+    if footer.toc_position < utils::HEADER_SIZE {
+        return Ok(vec![Problem::Unreadable(format!(
+            "footer: the ToC is said to be at {}, outside of the file",
+            footer.toc_position
+        ))]);
+    }
+    let toc = match parser::parse_toc(&toc, &version, footer.how_many) {
+        Ok(toc) => toc,
+        Err(e) => return Ok(vec![Problem::Unreadable(format!("ToC: {e}"))]),
+    };
+
+    let mut problems = vec![];
+    let valid_len = footer_start + footer.size;
+    if valid_len != file_len {
+        problems.push(Problem::IncompleteUpdate {
+            valid_len,
+            file_len,
+        });
+    }
+    let toc_end = footer.toc_position + u64::try_from(toc.len)?;
+    if toc_end != footer_start {
+        problems.push(Problem::TocMisplaced {
+            toc_end,
+            footer_start,
+        });
+    }
+
+    let mut names = HashSet::new();
+    for (index, entry) in (0_u64..).zip(toc.entries) {
+        let name = String::from_utf8_lossy(&entry.name).into_owned();
+        if std::str::from_utf8(&entry.name).is_err() {
+            problems.push(Problem::InvalidName {
+                index,
+                name: name.clone(),
+            });
+        }
+        if !names.insert(entry.name.clone()) {
+            problems.push(Problem::DuplicateName(name.clone()));
+        }
+        if let Some(problem) = verify_entry(r, name, &entry, file_len, footer.toc_position)? {
+            problems.push(problem);
+        }
+    }
+    Ok(problems)
+}
+
+// Check where the data of a single record is, and its checksum.
+fn verify_entry<R: Read + Seek>(
+    r: &mut R,
+    name: String,
+    entry: &RawEntry,
+    file_len: u64,
+    toc_position: u64,
+) -> Result<Option<Problem>> {
+    let (data_start, data_len) = (entry.data_start, entry.data_len);
+    match data_start.checked_add(data_len) {
+        Some(data_end) if data_start >= utils::HEADER_SIZE && data_end <= file_len => {
+            if data_len > 0 && data_end > toc_position {
+                return Ok(Some(Problem::OverlapsToc {
+                    name,
+                    data_start,
+                    data_len,
+                }));
+            }
+        }
+        _ => {
+            return Ok(Some(Problem::OutOfBounds {
+                name,
+                data_start,
+                data_len,
+            }))
+        }
+    }
+
+    if let Some(expected) = entry.checksum {
+        r.seek(SeekFrom::Start(data_start))?;
+        let mut crc = Crc32::new();
+        let mut data = r.take(data_len);
+        let mut buf = [0; 8192];
+        loop {
+            let bytes_read = data.read(&mut buf)?;
+            if bytes_read == 0 {
+                break;
+            }
+            crc.update(&buf[..bytes_read]);
+        }
+        let actual = crc.finish();
+        if actual != expected {
+            return Ok(Some(Problem::ChecksumMismatch {
+                name,
+                expected,
+                actual,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test::Tempfile;
+    use crate::utils::Record;
+    use crate::writers::Packer;
+
+    use std::io::{BufWriter, Cursor};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    // Build a 1.1 file by hand, with `data` right after the header and the
+    // given `ToC` entries (position, size, name).
+    fn build_1_1(data: &[u8], entries: &[(u64, u64, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut buff = vec![];
+        buff.extend_from_slice(b"SMPL");
+        buff.extend_from_slice(&[1, 1]);
+        buff.extend_from_slice(data);
+        let toc_position = buff.len() as u64;
+        for (pos, size, name) in entries {
+            buff.extend_from_slice(&pos.to_le_bytes());
+            buff.extend_from_slice(&size.to_le_bytes());
+            buff.push(u8::try_from(name.len()).unwrap());
+            buff.extend_from_slice(name);
+        }
+        buff.extend_from_slice(&toc_position.to_le_bytes());
+        buff.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        Cursor::new(buff)
+    }
+
+    #[test]
+    /// A file written by the `Packer` is sound.
+    fn verify_sound_file() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let packed_file = Tempfile::from_path(PathBuf::from_str("/tmp/verify_sound.bin")?);
+        crate::pack_records(
+            &*packed_file,
+            [
+                Record::new("c1".into(), vec![0x12, 0x34]),
+                Record::new("c2".into(), vec![]),
+            ]
+            .into_iter(),
+        )?;
+        crate::pack_records_update(
+            &*packed_file,
+            [Record::new("c3".into(), vec![0x56])].into_iter(),
+        )?;
+        assert_eq!(verify(&*packed_file)?, vec![]);
+
+        let mut sound = build_1_1(&[0x12, 0x34], &[(6, 2, b"c1"), (8, 0, b"c2")]);
+        assert_eq!(verify_reader(&mut sound)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    /// All the problems in the `ToC` are reported.
+    fn verify_bad_toc() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut buff = build_1_1(
+            &[0x12, 0x34],
+            &[
+                (6, 2, b"c1"),
+                (6, 2, b"c1"),
+                (2, 2, b"header"),
+                (7, 1000, b"too_big"),
+                (u64::MAX, 2, b"overflow"),
+                (7, 2, b"toc"),
+                (6, 1, &[0xFF, 0xFE]),
+            ],
+        );
+        assert_eq!(
+            verify_reader(&mut buff)?,
+            vec![
+                Problem::DuplicateName("c1".to_owned()),
+                Problem::OutOfBounds {
+                    name: "header".to_owned(),
+                    data_start: 2,
+                    data_len: 2
+                },
+                Problem::OutOfBounds {
+                    name: "too_big".to_owned(),
+                    data_start: 7,
+                    data_len: 1000
+                },
+                Problem::OutOfBounds {
+                    name: "overflow".to_owned(),
+                    data_start: u64::MAX,
+                    data_len: 2
+                },
+                Problem::OverlapsToc {
+                    name: "toc".to_owned(),
+                    data_start: 7,
+                    data_len: 2
+                },
+                Problem::InvalidName {
+                    index: 6,
+                    name: "\u{FFFD}\u{FFFD}".to_owned()
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Corrupted data is detected using the checksums.
+    fn verify_corrupted_data() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(Record::new("good".to_owned(), vec![0x12, 0x34]))?;
+            writer.write_record(Record::new("bad".to_owned(), vec![0x56, 0x78]))?;
+            writer.close()?;
+        }
+        buff.get_mut()[9] ^= 0x80;
+
+        let problems = verify_reader(&mut buff)?;
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(
+            problems.first(),
+            Some(Problem::ChecksumMismatch { name, .. }) if name == "bad"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    /// Files which can't be read at all are reported as such.
+    fn verify_unreadable() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut text = Cursor::new(b"This is just text.".to_vec());
+        assert!(matches!(
+            verify_reader(&mut text)?.as_slice(),
+            [Problem::Unreadable(_)]
+        ));
+
+        // The ToC is said to be after the footer.
+        let mut buff = build_1_1(&[0x12, 0x34], &[(6, 2, b"c1")]);
+        let len = buff.get_ref().len();
+        buff.get_mut()[len - 16..len - 8].copy_from_slice(&1000_u64.to_le_bytes());
+        assert!(matches!(
+            verify_reader(&mut buff)?.as_slice(),
+            [Problem::Unreadable(_)]
+        ));
+
+        // More entries than there are in the ToC.
+        let mut buff = build_1_1(&[0x12, 0x34], &[(6, 2, b"c1")]);
+        let len = buff.get_ref().len();
+        buff.get_mut()[len - 8..].copy_from_slice(&2_u64.to_le_bytes());
+        assert!(matches!(
+            verify_reader(&mut buff)?.as_slice(),
+            [Problem::Unreadable(_)]
+        ));
+
+        // Less entries than there are in the ToC.
+        let mut buff = build_1_1(&[0x12, 0x34], &[(6, 1, b"c1"), (7, 1, b"c2")]);
+        let len = buff.get_ref().len();
+        buff.get_mut()[len - 8..].copy_from_slice(&1_u64.to_le_bytes());
+        assert!(matches!(
+            verify_reader(&mut buff)?.as_slice(),
+            [Problem::TocMisplaced { .. }]
+        ));
+        Ok(())
+    }
+}
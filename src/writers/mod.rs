@@ -1,16 +1,30 @@
-#[allow(unused)]
+pub mod packer;
+#[cfg(test)]
 pub mod ver_1_0;
+#[cfg(test)]
 pub mod ver_1_1;
+#[cfg(test)]
+pub mod ver_1_2;
+#[cfg(test)]
+pub mod ver_1_3;
+#[cfg(test)]
+pub mod ver_1_4;
+#[cfg(test)]
+pub mod ver_1_5;
+#[cfg(test)]
+pub mod ver_1_6;
+pub mod ver_1_7;
 
-/// version 11 is the default one;
-pub use ver_1_1::*;
+pub use packer::{PackSummary, RecordStep, TocEntry};
+/// version 17 is the default one;
+pub use ver_1_7::*;
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::utils;
 
-    use std::io::{BufWriter, Cursor};
+    use std::io::{BufWriter, Cursor, Read, Write};
 
     #[test]
     /// We can't use the same record name twice.
@@ -36,12 +50,12 @@ mod test {
     }
 
     #[test]
-    /// We must use a "short" record name.
+    /// Up to version 1.6, we must use a "short" record name.
     fn record_name_too_long() -> std::result::Result<(), Box<dyn std::error::Error>> {
         let mut buff = Cursor::new(vec![]);
 
         let buffwriter = BufWriter::new(&mut buff);
-        let mut writer = Packer::from_writer(buffwriter).write_header()?;
+        let mut writer = ver_1_6::Packer::from_writer(buffwriter).write_header()?;
 
         // This record's name is 255 char long.
         let res = writer
@@ -61,4 +75,440 @@ mod test {
         writer.close()?;
         Ok(())
     }
+
+    #[test]
+    /// Old versions refuse what they can't store, instead of dropping it.
+    fn old_versions_features() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut metadata = crate::metadata::Metadata::new();
+        metadata.insert("mode", crate::metadata::MetadataValue::U64(0o644));
+
+        let mut writer = ver_1_3::Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        let res = writer.write_compressed_record(
+            utils::Record::new("name".to_owned(), vec![0; 100]),
+            crate::compression::Compression::Lz,
+        );
+        assert!(matches!(
+            res,
+            Err(crate::error::EasypackError::UnsupportedCompression(_))
+        ));
+        writer.close()?;
+
+        let mut writer = ver_1_4::Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        let res = writer.write_record(
+            utils::Record::new("name".to_owned(), vec![0; 100]).with_metadata(metadata.clone()),
+        );
+        assert!(matches!(
+            res,
+            Err(crate::error::EasypackError::InvalidMetadata(_))
+        ));
+        writer.close()?;
+
+        let mut writer = ver_1_5::Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        let res = writer.set_metadata(metadata);
+        assert!(matches!(
+            res,
+            Err(crate::error::EasypackError::InvalidMetadata(_))
+        ));
+        writer.close()?;
+        Ok(())
+    }
+
+    #[test]
+    /// Records can be written from any reader, or a piece at a time.
+    fn write_record_streaming() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(utils::Record::new("first".to_owned(), vec![0x01, 0x02]))?;
+            // Bigger than any buffer around, so that it is copied in chunks.
+            writer.write_record_from_reader(
+                "from_reader".to_owned(),
+                std::io::repeat(0x42).take(1_000_000),
+            )?;
+            let mut record_writer = writer.begin_record("incremental".to_owned())?;
+            record_writer.write_all(&[0x12, 0x34])?;
+            record_writer.write_all(&[0x56])?;
+            record_writer.finish_record()?;
+            writer.write_record(utils::Record::new("last".to_owned(), vec![0x03]))?;
+            writer.close()?;
+        }
+
+        let mut archive = crate::Archive::from_reader(buff)?;
+        assert_eq!(archive.len(), 4);
+        assert_eq!(archive.get("first")?.unwrap().data, vec![0x01, 0x02]);
+        assert_eq!(
+            archive.get("from_reader")?.unwrap().data,
+            vec![0x42; 1_000_000]
+        );
+        assert_eq!(
+            archive.get("incremental")?.unwrap().data,
+            vec![0x12, 0x34, 0x56]
+        );
+        assert_eq!(archive.get("last")?.unwrap().data, vec![0x03]);
+        Ok(())
+    }
+
+    #[test]
+    /// Names can be longer than 255 bytes, whatever the way the records are
+    /// written.
+    fn record_name_long() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let names = [
+            "x".repeat(256),
+            format!("{}/file.txt", "deep/path".repeat(100)),
+            "y".repeat(70_000),
+        ];
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(utils::Record::new(names[0].clone(), vec![0x12]))?;
+            writer.write_record_from_reader(names[1].clone(), &[0x34][..])?;
+            writer.write_compressed_record(
+                utils::Record::new(names[2].clone(), vec![0x56; 100]),
+                crate::Compression::Lz,
+            )?;
+            writer.write_record(utils::Record::new("short".to_owned(), vec![0x78]))?;
+            writer.close()?;
+        }
+
+        let archive = crate::Archive::from_bytes(buff.get_ref())?;
+        assert_eq!(
+            archive.names().collect::<Vec<_>>(),
+            [&names[0], &names[1], &names[2], "short"]
+        );
+        let mut archive = crate::Archive::from_reader(buff)?;
+        assert_eq!(archive.get(&names[0])?.unwrap().data, vec![0x12]);
+        assert_eq!(archive.get(&names[1])?.unwrap().data, vec![0x34]);
+        assert_eq!(archive.get(&names[2])?.unwrap().data, vec![0x56; 100]);
+        assert_eq!(archive.get("short")?.unwrap().data, vec![0x78]);
+        Ok(())
+    }
+
+    #[test]
+    /// Names are checked before writing any data.
+    fn begin_record_invalid_name() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(utils::Record::new("name".to_owned(), vec![0x12]))?;
+            assert!(writer.begin_record("name".to_owned()).is_err());
+            assert!(writer
+                .write_record_from_reader("name".to_owned(), &[0x34, 0x56][..])
+                .is_err());
+            writer.close()?;
+        }
+
+        let mut archive = crate::Archive::from_reader(buff)?;
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.get("name")?.unwrap().data, vec![0x12]);
+        Ok(())
+    }
+
+    #[test]
+    /// A record which is not finished is simply not there.
+    fn record_not_finished() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            {
+                let mut record_writer = writer.begin_record("unfinished".to_owned())?;
+                record_writer.write_all(&[0x12, 0x34])?;
+            }
+            writer.write_record(utils::Record::new("finished".to_owned(), vec![0x56]))?;
+            writer.close()?;
+        }
+
+        let mut archive = crate::Archive::from_reader(buff)?;
+        assert_eq!(archive.len(), 1);
+        assert!(!archive.contains("unfinished"));
+        assert_eq!(archive.get("finished")?.unwrap().data, vec![0x56]);
+        Ok(())
+    }
+
+    #[test]
+    /// An aborted Packer gives the writer back, with no readable archive in it.
+    fn packer_abort() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut writer = Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        writer.write_record(utils::Record::new("name".to_owned(), vec![0x12]))?;
+        let buff = writer.abort();
+        assert!(!buff.get_ref().is_empty());
+        assert!(crate::Archive::from_reader(buff).is_err());
+
+        let mut writer = ver_1_1::Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        writer.write_record(utils::Record::new("name".to_owned(), vec![0x12]))?;
+        assert!(crate::Archive::from_reader(writer.abort()).is_err());
+        Ok(())
+    }
+
+    /// A writer which fails once `capacity` bytes are written.
+    struct FailingWriter {
+        capacity: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.capacity == 0 {
+                return Err(std::io::Error::other("no space left"));
+            }
+            let len = buf.len().min(self.capacity);
+            self.capacity -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    /// An error while packing is given back as an error, and the Packer can
+    /// be dropped afterwards without panicking.
+    fn packer_write_error() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        fn pack<W: Write>(writer: W) -> crate::error::Result<()> {
+            let mut writer = Packer::from_writer(writer).write_header()?;
+            writer.write_record(utils::Record::new("c1".to_owned(), vec![0x12; 64]))?;
+            writer.write_record(utils::Record::new("c2".to_owned(), vec![0x34; 64]))?;
+            writer.close()?;
+            Ok(())
+        }
+
+        // The error can happen in the header, the data, or the `ToC`.
+        for capacity in [2, 40, 130, 150] {
+            let res = pack(FailingWriter { capacity });
+            assert!(
+                matches!(res, Err(crate::error::EasypackError::IoError(_))),
+                "{capacity}: {res:?}"
+            );
+        }
+        assert!(pack(FailingWriter { capacity: 1024 }).is_ok());
+
+        // The same with a record written piece by piece.
+        let mut writer =
+            ver_1_1::Packer::from_writer(FailingWriter { capacity: 16 }).write_header()?;
+        let mut record_writer = writer.begin_record("c1".to_owned())?;
+        assert!(record_writer.write_all(&[0x12; 64]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Closing the Packer gives the writer back, with what was written.
+    fn close_gives_writer_back() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut writer = Packer::from_writer(Cursor::new(vec![])).write_header()?;
+        writer.write_record(utils::Record::new("c1".to_owned(), vec![0x12, 0x34]))?;
+        writer.write_record(utils::Record::new("c2".to_owned(), vec![0x56]))?;
+        let (buff, summary) = writer.close()?;
+        let data = buff.into_inner();
+        assert_eq!(
+            summary,
+            PackSummary {
+                records: 2,
+                toc_position: 9,
+                total_size: data.len().try_into()?,
+            }
+        );
+        let archive = crate::Archive::from_bytes(&data)?;
+        assert_eq!(archive.get("c2")?.as_deref(), Some([0x56].as_slice()));
+
+        // When appending, the data already there is counted too.
+        let version = crate::readers::read_header(&mut Cursor::new(&data))?;
+        let toc = archive
+            .entries()
+            .map(|e| TocEntry::new(e.name.clone(), e.data_start, e.data_len, 0))
+            .collect();
+        let size = summary.total_size;
+        let mut writer =
+            Packer::from_writer(Cursor::new(vec![])).append_mode(toc, size, &version)?;
+        writer.write_record(utils::Record::new("c3".to_owned(), vec![0x78]))?;
+        let (buff, summary) = writer.close()?;
+        assert_eq!(summary.records, 3);
+        assert_eq!(summary.toc_position, size + 1);
+        assert_eq!(
+            summary.total_size,
+            size + u64::try_from(buff.get_ref().len())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Compressed records are read back as they were written, whatever the
+    /// way they are read.
+    fn write_compressed_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::Compression;
+        use std::io::{Seek, SeekFrom};
+
+        let text = b"{\"name\": \"record\", \"value\": 42}\n".repeat(100);
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_compressed_record(
+                utils::Record::new("text".to_owned(), text.clone()),
+                Compression::Lz,
+            )?;
+            // Not worth compressing: stored as it is.
+            writer.write_compressed_record(
+                utils::Record::new("short".to_owned(), vec![0x12, 0x34]),
+                Compression::Lz,
+            )?;
+            writer.write_compressed_record(
+                utils::Record::new("empty".to_owned(), vec![]),
+                Compression::Lz,
+            )?;
+            writer.write_record(utils::Record::new("raw".to_owned(), text.clone()))?;
+            for method in [Compression::Deflate, Compression::Zstd] {
+                let record = utils::Record::new(method.to_string(), text.clone());
+                if method.is_available() {
+                    writer.write_compressed_record(record, method)?;
+                } else {
+                    assert!(matches!(
+                        writer.write_compressed_record(record, method),
+                        Err(crate::EasypackError::UnsupportedCompression(_))
+                    ));
+                }
+            }
+            writer.close()?;
+        }
+
+        let archive = crate::Archive::from_bytes(buff.get_ref())?;
+        let methods: Vec<_> = archive
+            .entries()
+            .map(|entry| (entry.name.as_str(), entry.compression))
+            .collect();
+        assert_eq!(
+            methods[..4],
+            [
+                ("text", Compression::Lz),
+                ("short", Compression::None),
+                ("empty", Compression::None),
+                ("raw", Compression::None),
+            ]
+        );
+        let text_entry = archive.entries().next().unwrap();
+        assert!(text_entry.data_len < text.len() as u64 / 10);
+        assert_eq!(text_entry.uncompressed_len, text.len() as u64);
+        for name in archive.names() {
+            let expected = if name == "short" {
+                vec![0x12, 0x34]
+            } else if name == "empty" {
+                vec![]
+            } else {
+                text.clone()
+            };
+            assert_eq!(archive.get(name)?.as_deref(), Some(expected.as_slice()));
+        }
+
+        let mut archive = crate::Archive::from_reader(buff)?;
+        assert_eq!(archive.get("text")?.unwrap().data, text);
+        let mut reader = archive.open_record("text")?.unwrap();
+        assert_eq!(reader.len(), text.len() as u64);
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        assert_eq!(data, text);
+        reader.seek(SeekFrom::Start(1))?;
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf)?;
+        assert_eq!(buf, text[1..5]);
+        Ok(())
+    }
+
+    #[test]
+    /// The metadata of the records is read back as it was written, whatever
+    /// the way the records are written.
+    fn write_records_metadata() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::{Compression, Metadata, MetadataValue};
+
+        let metadata = Metadata::new()
+            .with_mtime(1_234_567_890)
+            .with_mode(0o644)
+            .with_content_type("application/json")
+            .with_value("author", MetadataValue::Text("someone".to_owned()))
+            .with_value("", MetadataValue::U64(u64::MAX));
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.write_record(
+                utils::Record::new("plain".to_owned(), vec![0x12]).with_metadata(metadata.clone()),
+            )?;
+            writer.write_compressed_record(
+                utils::Record::new("compressed".to_owned(), vec![0x42; 100])
+                    .with_metadata(metadata.clone()),
+                Compression::Lz,
+            )?;
+            let mut record_writer = writer.begin_record("streamed".to_owned())?;
+            record_writer.write_all(&[0x34])?;
+            record_writer.set_metadata(metadata.clone());
+            record_writer.finish_record()?;
+            writer.write_record(utils::Record::new("none".to_owned(), vec![0x56]))?;
+            // Keys are at most 255 bytes long.
+            let long_key = Metadata::new().with_value("x".repeat(256), MetadataValue::U64(0));
+            assert!(matches!(
+                writer.write_record(
+                    utils::Record::new("long_key".to_owned(), vec![]).with_metadata(long_key)
+                ),
+                Err(crate::EasypackError::InvalidMetadata(_))
+            ));
+            writer.close()?;
+        }
+
+        let mut archive = crate::Archive::from_reader(buff)?;
+        assert!(!archive.contains("long_key"));
+        for name in ["plain", "compressed", "streamed"] {
+            assert_eq!(archive.get(name)?.unwrap().metadata, metadata);
+        }
+        assert!(archive.get("none")?.unwrap().metadata.is_empty());
+        let entry = archive.entries().next().unwrap();
+        assert_eq!(entry.metadata.mtime(), Some(1_234_567_890));
+        assert_eq!(entry.metadata.mode(), Some(0o644));
+        assert_eq!(entry.metadata.content_type(), Some("application/json"));
+        Ok(())
+    }
+
+    #[test]
+    /// The metadata of the archive is read back as it was last set, wherever
+    /// the archive is read from.
+    fn write_archive_metadata() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::{Metadata, MetadataValue};
+
+        let mut buff = Cursor::new(vec![]);
+        {
+            let buffwriter = BufWriter::new(&mut buff);
+            let mut writer = Packer::from_writer(buffwriter).write_header()?;
+            writer.set_metadata(Metadata::new().with_mtime(42))?;
+            writer.write_record(utils::Record::new("name".to_owned(), vec![0x12]))?;
+            assert!(matches!(
+                writer.set_metadata(
+                    Metadata::new().with_value("x".repeat(256), MetadataValue::U64(0))
+                ),
+                Err(crate::EasypackError::InvalidMetadata(_))
+            ));
+            writer.set_metadata(
+                Metadata::new()
+                    .with_value("tool", MetadataValue::Text("easypack 0.1".to_owned()))
+                    .with_value("description", MetadataValue::Text(String::new())),
+            )?;
+            assert_eq!(writer.metadata().len(), 2);
+            writer.close()?;
+        }
+
+        let archive = crate::Archive::from_bytes(buff.get_ref())?;
+        assert_eq!(archive.metadata().len(), 2);
+        assert_eq!(
+            archive.metadata().get("tool"),
+            Some(&MetadataValue::Text("easypack 0.1".to_owned()))
+        );
+        assert_eq!(archive.get("name")?.as_deref(), Some([0x12].as_slice()));
+
+        let mut archive = crate::Archive::from_reader(buff)?;
+        assert_eq!(archive.metadata().mtime(), None);
+        assert_eq!(
+            archive.metadata().get("description"),
+            Some(&MetadataValue::Text(String::new()))
+        );
+        assert_eq!(archive.get("name")?.unwrap().data, vec![0x12]);
+        Ok(())
+    }
 }
@@ -0,0 +1,657 @@
+/*!
+The `Packer`, shared by all the versions of the file format.
+
+Every version writes the header and the records the same way: what changes is
+what the records can carry (compression, metadata), and how the `ToC` and the footer are
+written. This is what the `PackFormat` of each version tells.
+*/
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use crate::checksum::{crc32, Crc32};
+use crate::compression::Compression;
+use crate::error::{EasypackError, Result};
+use crate::metadata::{Metadata, MetadataValue};
+use crate::utils::{self, Version};
+
+pub trait Steps {}
+
+macro_rules! writersteps {
+    ($name: tt) => {
+        pub struct $name {}
+        impl Steps for $name {}
+    };
+}
+
+writersteps!(NoneStep);
+writersteps!(HeaderStep);
+writersteps!(RecordStep);
+
+/// What changes from a version of the file format to another.
+pub trait PackFormat {
+    /// The version, as written in the header.
+    const VERSION: (u8, u8);
+    /// The longest record name which can be written, in bytes.
+    const MAX_NAME_LEN: u64;
+    /// Whether the data of the records can be compressed.
+    const COMPRESSION: bool;
+    /// Whether the records can have their metadata.
+    const RECORD_METADATA: bool;
+    /// Whether the archive can have its metadata.
+    const ARCHIVE_METADATA: bool;
+
+    /// Write the `ToC`: the metadata of the archive, if the version has it,
+    /// and the entries.
+    /// This function returns the amount of bytes being written.
+    /// # Errors
+    /// Any IO error, or if an entry can't be written with this version.
+    fn write_toc<W: Write>(w: &mut W, toc: Vec<TocEntry>, metadata: &Metadata) -> Result<u64>;
+
+    /// Write the footer, which tells where to find the `ToC`. The checksum
+    /// is the one of the whole `ToC`, for the versions that store it.
+    /// This function returns the amount of bytes being written.
+    /// # Errors
+    /// Any IO error.
+    fn write_footer<W: Write>(
+        w: &mut W,
+        toc_position: u64,
+        how_many: u64,
+        toc_checksum: u32,
+    ) -> Result<u64>;
+}
+
+#[derive(Debug)]
+pub struct TocEntry {
+    record_name: String,
+    data_start: u64,
+    data_len: u64,
+    checksum: u32,
+    compression: Compression,
+    uncompressed_len: u64,
+    metadata: Metadata,
+}
+
+impl TocEntry {
+    pub const fn new(record_name: String, data_start: u64, data_len: u64, checksum: u32) -> Self {
+        Self {
+            record_name,
+            data_start,
+            data_len,
+            checksum,
+            compression: Compression::None,
+            uncompressed_len: data_len,
+            metadata: Metadata::new(),
+        }
+    }
+
+    #[must_use]
+    /// The same entry, with its data compressed.
+    pub const fn with_compression(
+        mut self,
+        compression: Compression,
+        uncompressed_len: u64,
+    ) -> Self {
+        self.compression = compression;
+        self.uncompressed_len = uncompressed_len;
+        self
+    }
+
+    #[must_use]
+    /// The same entry, with the given `metadata`.
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    #[must_use]
+    /// The name of the record.
+    pub fn name(&self) -> &str {
+        &self.record_name
+    }
+
+    // @TODO: This clippy report seems wrong, report?
+    #[allow(clippy::missing_const_for_fn)]
+    pub(super) fn extract(self) -> (u64, u64, u32, Compression, u64, Metadata, String) {
+        (
+            self.data_start,
+            self.data_len,
+            self.checksum,
+            self.compression,
+            self.uncompressed_len,
+            self.metadata,
+            self.record_name,
+        )
+    }
+}
+
+/**
+The `Packer`, implemented as an easy state machine to prevent API misuse.
+
+# Usage.
+
+- create the packer using `from_writer`;
+- write the headers using `write_header`;
+- set the metadata of the archive with `set_metadata`, at any time before
+  `close`;
+- write each record using `write_record`, or `write_record_from_reader` and
+  `begin_record` to avoid loading all the record's data in memory;
+- or compress it with `write_compressed_record`;
+- set the metadata of a record with `Record::with_metadata`, or
+  `RecordWriter::set_metadata`;
+- replace the data of a record already written, using `replace_record`;
+- write the `ToC` and the footer using `close`, which gives the writer back.
+
+If `close` is not called, e.g. because of an error while writing, the written
+data has no `ToC` and can't be read back: use `abort` to give up on it
+explicitly, and get the writer back.
+*/
+#[must_use = "The packed data can't be read back until `close` is called"]
+pub struct Packer<S: Steps, W: Write, F: PackFormat> {
+    // This is the writing position. It is needed to know where we are in the
+    // file.
+    pos: u64,
+    // Note: behind an option, so that it can be moved out of the Packer.
+    writer: Option<W>,
+    _step: PhantomData<S>,
+    _format: PhantomData<F>,
+    // The TableOfContent (`ToC`), filled in when a record is written.
+    // Note: behind an option, so that it can be moved out of the Packer.
+    toc: Option<Vec<TocEntry>>,
+    // The names already in the `ToC`, with where to find them in it, to
+    // quickly check they are not reused.
+    names: HashMap<String, usize>,
+    // The metadata of the archive, written with the `ToC`.
+    metadata: Metadata,
+}
+
+impl<W: Write, F: PackFormat> Packer<NoneStep, W, F> {
+    /// Create a Packer, writing data using the given writer.
+    pub fn from_writer(writer: W) -> Packer<HeaderStep, W, F> {
+        Packer {
+            pos: 0,
+            writer: Some(writer),
+            _step: PhantomData,
+            _format: PhantomData,
+            toc: Some(vec![]),
+            names: HashMap::new(),
+            metadata: Metadata::new(),
+        }
+    }
+}
+
+impl<W: Write, F: PackFormat> Packer<HeaderStep, W, F> {
+    /// Append to a given, already loaded, file.
+    /// This function can be used in case we have already read the `Toc` of
+    /// another file, and we simply want to append to it.
+    /// # Errors
+    /// Any IO error.
+    /// If the version of the original file does not match this one.
+    pub fn append_mode(
+        &mut self,
+        toc: Vec<TocEntry>,
+        file_size: u64,
+        old_version: &Version,
+    ) -> Result<Packer<RecordStep, W, F>> {
+        if old_version != &F::VERSION.into() {
+            return Err(EasypackError::InvalidFileError(format!(
+                "Cannot append to version {old_version:?}, expected {:?}",
+                F::VERSION
+            )));
+        }
+        let names = toc
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.record_name.clone(), i))
+            .collect();
+        Ok(Packer {
+            pos: file_size,
+            writer: self.writer.take(),
+            _step: PhantomData,
+            _format: PhantomData,
+            toc: Some(toc),
+            names,
+            metadata: std::mem::take(&mut self.metadata),
+        })
+    }
+
+    /// Write the header of the file.
+    /// The `ToC` is located at the bottom of the file, and we want to keep it
+    /// like that since this allow us to expand the file without the need to
+    /// rewrite it completely - we can just load the old `ToC`, change it / add
+    /// new records, and write it back at the end. The old table will be
+    /// ignored this way, but there won't be anything else other than some disk
+    /// space lost.
+    /// # Errors
+    /// Any IO error.
+    /// # Panics
+    /// Never, the writer is always there when the Packer is built.
+    pub fn write_header(&mut self) -> Result<Packer<RecordStep, W, F>> {
+        write_header(&mut self.writer.as_mut().expect("Writer is expected to be Some since the only way to construct the Packer is via `from_writer`"), F::VERSION)?;
+        Ok(Packer {
+            pos: self.pos + utils::HEADER_SIZE,
+            writer: self.writer.take(),
+            _step: PhantomData,
+            _format: PhantomData,
+            toc: self.toc.take(),
+            names: std::mem::take(&mut self.names),
+            metadata: std::mem::take(&mut self.metadata),
+        })
+    }
+}
+
+impl<W: Write, F: PackFormat> Packer<RecordStep, W, F> {
+    /// Set the metadata of the archive, replacing the one set before if any.
+    /// When appending, it starts as the metadata already in the file.
+    /// # Errors
+    /// In case the metadata is invalid, or this version can't store it.
+    pub fn set_metadata(&mut self, metadata: Metadata) -> Result<()> {
+        if !F::ARCHIVE_METADATA && !metadata.is_empty() {
+            return Err(EasypackError::InvalidMetadata(format!(
+                "Version {:?} can't store the metadata of the archive",
+                F::VERSION
+            )));
+        }
+        check_metadata(&metadata)?;
+        self.metadata = metadata;
+        Ok(())
+    }
+
+    #[must_use]
+    /// The metadata of the archive, as it will be written.
+    pub const fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Write a single record.
+    /// This function internally update the `ToC`, that is written with the
+    /// `close` call.
+    /// # Errors
+    /// In case the record's name is invalid, or the same as another already
+    /// inserted record.
+    /// # Panics
+    /// Never, the writer and the `ToC` are always there in this step.
+    pub fn write_record(&mut self, record: utils::Record) -> Result<()> {
+        self.write_compressed_record(record, Compression::None)
+    }
+
+    /// Write a single record, compressing its data with `compression`.
+    /// If the compressed data is not smaller, the data is written as it is.
+    /// # Errors
+    /// In case the record's name or metadata is invalid, or the name is the
+    /// same as another already inserted record. If the compression method is
+    /// not available, or this version can't compress the records. Any IO
+    /// error.
+    pub fn write_compressed_record(
+        &mut self,
+        record: utils::Record,
+        compression: Compression,
+    ) -> Result<()> {
+        self.check_record_name(&record.name)?;
+        check_record_metadata::<F>(&record.metadata)?;
+        let entry = self
+            .write_data(record.name, &record.data, compression)?
+            .with_metadata(record.metadata);
+        self.push_toc_entry(entry);
+        Ok(())
+    }
+
+    /// Write a single record, which replaces the one with the same name, if
+    /// any. The old data stays in the file, but the `ToC` entry is repointed
+    /// to the new one, keeping its place in the `ToC`. If there is no record
+    /// with the same name, this is the same as `write_record`.
+    /// # Errors
+    /// In case the record's name or metadata is invalid. Any IO error.
+    /// # Panics
+    /// Never, the writer and the `ToC` are always there in this step.
+    pub fn replace_record(&mut self, record: utils::Record) -> Result<()> {
+        let Some(&i) = self.names.get(&record.name) else {
+            return self.write_record(record);
+        };
+        check_record_metadata::<F>(&record.metadata)?;
+
+        let entry = self
+            .write_data(record.name, &record.data, Compression::None)?
+            .with_metadata(record.metadata);
+        *self
+            .toc
+            .as_mut()
+            .and_then(|toc| toc.get_mut(i))
+            .expect("The entry is in the ToC, since its name is indexed.") = entry;
+        Ok(())
+    }
+
+    // Write the data of a record, compressed with `compression` if it gets
+    // smaller, and get its entry for the `ToC`.
+    fn write_data(
+        &mut self,
+        name: String,
+        data: &[u8],
+        compression: Compression,
+    ) -> Result<TocEntry> {
+        let compressed = match compression {
+            Compression::None => None,
+            _ if !F::COMPRESSION => {
+                return Err(EasypackError::UnsupportedCompression(format!(
+                    "Version {:?} can't compress the records",
+                    F::VERSION
+                )))
+            }
+            _ => Some(compression.compress(data)?).filter(|c| c.len() < data.len()),
+        };
+
+        let data_start = self.pos;
+        let writer = self.writer.as_mut().expect(
+            "Writer is Some, since otherwise we should have panicked when writing the headers.",
+        );
+        let entry = if let Some(compressed) = compressed {
+            writer.write_all(&compressed)?;
+            TocEntry::new(
+                name,
+                data_start,
+                compressed.len() as u64,
+                crc32(&compressed),
+            )
+            .with_compression(compression, data.len() as u64)
+        } else {
+            writer.write_all(data)?;
+            TocEntry::new(name, data_start, data.len() as u64, crc32(data))
+        };
+        self.pos = data_start + entry.data_len;
+        Ok(entry)
+    }
+
+    /// Write a single record, taking its data from `reader` until it is
+    /// exhausted.
+    /// The data is copied in chunks, so it is never loaded all in memory.
+    /// # Errors
+    /// In case the record's name is invalid, or the same as another already
+    /// inserted record. Any IO error.
+    pub fn write_record_from_reader(&mut self, name: String, mut reader: impl Read) -> Result<()> {
+        let mut record_writer = self.begin_record(name)?;
+        std::io::copy(&mut reader, &mut record_writer)?;
+        record_writer.finish_record()
+    }
+
+    /// Start writing a single record, whose data is written incrementally
+    /// using the returned `RecordWriter`. The record is added to the `ToC`
+    /// only when `RecordWriter::finish_record` is called.
+    /// # Errors
+    /// In case the record's name is invalid, or the same as another already
+    /// inserted record.
+    pub fn begin_record(&mut self, name: String) -> Result<RecordWriter<'_, W, F>> {
+        self.check_record_name(&name)?;
+        Ok(RecordWriter {
+            data_start: self.pos,
+            packer: self,
+            name,
+            checksum: Crc32::new(),
+            metadata: Metadata::new(),
+        })
+    }
+
+    // Check that a new record can be named `name`.
+    fn check_record_name(&self, name: &str) -> Result<()> {
+        if self.names.contains_key(name) {
+            return Err(EasypackError::RecordSameName(format!(
+                "Name {name} has already been used."
+            )));
+        }
+        if name.len() as u64 > F::MAX_NAME_LEN {
+            return Err(EasypackError::RecordNameTooBig(format!(
+                "Unable to write a record with name len > {} bytes.",
+                F::MAX_NAME_LEN
+            )));
+        }
+        Ok(())
+    }
+
+    fn push_toc_entry(&mut self, entry: TocEntry) {
+        let toc = self
+            .toc
+            .as_mut()
+            .expect("ToC is Some here, we built it in the Header step.");
+        self.names.insert(entry.record_name.clone(), toc.len());
+        toc.push(entry);
+    }
+
+    /// Give up on the packed data, without writing the `ToC`: the data
+    /// written so far can't be read back. The writer is given back as it is,
+    /// e.g. to remove the file.
+    /// # Panics
+    /// Never, the writer is always there in this step.
+    pub fn abort(mut self) -> W {
+        self.writer
+            .take()
+            .expect("Writer is Some here, by construction.")
+    }
+
+    /// Write the toc, the footer, and consume the Packer.
+    /// The writer is given back, flushed, with a summary of what was written,
+    /// e.g. to get the bytes back from a `Cursor`, or to `sync_all` a file.
+    /// # Errors
+    /// Any IO error.
+    /// # Panics
+    /// Never, the writer and the `ToC` are always there in this step.
+    pub fn close(mut self) -> Result<(W, PackSummary)> {
+        let table_pos = self.pos;
+        let toc = self
+            .toc
+            .take()
+            .expect("ToC is Some here, we built it in the Header step.");
+        let how_many = toc.len() as u64;
+        let mut writer = self
+            .writer
+            .take()
+            .expect("Writer is Some here, by construction.");
+
+        // The `ToC` goes through the checksum on its way to the writer.
+        let mut toc_writer = ChecksumWriter {
+            inner: &mut writer,
+            checksum: Crc32::new(),
+        };
+        self.pos += F::write_toc(&mut toc_writer, toc, &self.metadata)?;
+        let toc_checksum = toc_writer.checksum.finish();
+
+        // Then the last bytes tells where to find the toc in the file itself.
+        self.pos += F::write_footer(&mut writer, table_pos, how_many, toc_checksum)?;
+        // Make sure buffered writers don't swallow errors when dropped.
+        writer.flush()?;
+
+        Ok((
+            writer,
+            PackSummary {
+                records: how_many,
+                toc_position: table_pos,
+                total_size: self.pos,
+            },
+        ))
+    }
+}
+
+/// What was written by `Packer::close`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackSummary {
+    /// The number of records in the `ToC`.
+    pub records: u64,
+    /// Where the `ToC` starts.
+    pub toc_position: u64,
+    /// The size of the whole packed data, from the header to the footer. When
+    /// appending, this includes the data which was already there.
+    pub total_size: u64,
+}
+
+/**
+The writer of a single record, created by `Packer::begin_record`.
+
+The data is written straight to the packer's writer, and its length and
+checksum are computed along the way. Once all the data is written, `finish_record` adds the
+record to the `ToC`. If the `RecordWriter` is dropped instead, the data written
+so far stays in the file but no record points to it.
+*/
+pub struct RecordWriter<'p, W: Write, F: PackFormat> {
+    packer: &'p mut Packer<RecordStep, W, F>,
+    name: String,
+    data_start: u64,
+    checksum: Crc32,
+    metadata: Metadata,
+}
+
+impl<W: Write, F: PackFormat> RecordWriter<'_, W, F> {
+    /// Set the metadata of the record, replacing the one set before if any.
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
+    /// Add the record to the `ToC`, with all the data written so far.
+    /// # Errors
+    /// If the metadata is invalid: the data written so far stays in the file,
+    /// but no record points to it.
+    pub fn finish_record(self) -> Result<()> {
+        check_record_metadata::<F>(&self.metadata)?;
+        let data_len = self.packer.pos - self.data_start;
+        self.packer.push_toc_entry(
+            TocEntry::new(self.name, self.data_start, data_len, self.checksum.finish())
+                .with_metadata(self.metadata),
+        );
+        Ok(())
+    }
+}
+
+impl<W: Write, F: PackFormat> Write for RecordWriter<'_, W, F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self
+            .packer
+            .writer
+            .as_mut()
+            .expect("Writer is Some here, by construction.")
+            .write(buf)?;
+        self.checksum.update(&buf[..written]);
+        self.packer.pos += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.packer
+            .writer
+            .as_mut()
+            .expect("Writer is Some here, by construction.")
+            .flush()
+    }
+}
+
+/// The header is the magic number, and the version.
+/// # Errors
+/// Any IO error.
+pub fn write_header<W: Write>(w: &mut W, version: (u8, u8)) -> Result<()> {
+    w.write_all(utils::FILE_TYPE.as_bytes())?;
+    // Write version.
+    w.write_all(&version.0.to_le_bytes())?;
+    w.write_all(&version.1.to_le_bytes())?;
+    Ok(())
+}
+
+/// The footer of the versions since 1.3: the position of the `ToC`, the number
+/// of records, the checksum of the `ToC`, the checksum of these 3 fields, and
+/// the magic number.
+/// This function returns the amount of bytes being written.
+/// # Errors
+/// Any IO error.
+pub(super) fn write_checked_footer<W: Write>(
+    w: &mut W,
+    toc_position: u64,
+    how_many: u64,
+    toc_checksum: u32,
+) -> Result<u64> {
+    let mut footer = Vec::with_capacity(CHECKED_FOOTER_SIZE);
+    footer.extend_from_slice(&toc_position.to_le_bytes());
+    footer.extend_from_slice(&how_many.to_le_bytes());
+    footer.extend_from_slice(&toc_checksum.to_le_bytes());
+    footer.extend_from_slice(&crc32(&footer).to_le_bytes());
+    footer.extend_from_slice(utils::FOOTER_MAGIC.as_bytes());
+    w.write_all(&footer)?;
+    Ok(CHECKED_FOOTER_SIZE as u64)
+}
+
+/// The size of the footer of the versions since 1.3.
+const CHECKED_FOOTER_SIZE: usize = 28;
+
+// A writer computing the checksum of all the data going through it.
+struct ChecksumWriter<'w, W: Write> {
+    inner: &'w mut W,
+    checksum: Crc32,
+}
+
+impl<W: Write> Write for ChecksumWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.checksum.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Check that `metadata` can be stored for a record with the format `F`.
+fn check_record_metadata<F: PackFormat>(metadata: &Metadata) -> Result<()> {
+    if !F::RECORD_METADATA && !metadata.is_empty() {
+        return Err(EasypackError::InvalidMetadata(format!(
+            "Version {:?} can't store the metadata of the records",
+            F::VERSION
+        )));
+    }
+    check_metadata(metadata)
+}
+
+// Check that `metadata` can be stored in the `ToC`.
+fn check_metadata(metadata: &Metadata) -> Result<()> {
+    if u16::try_from(metadata.len()).is_err() {
+        return Err(EasypackError::InvalidMetadata(format!(
+            "Too many metadata pairs: {}, while only up to {} are allowed",
+            metadata.len(),
+            u16::MAX
+        )));
+    }
+    if let Some((key, _)) = metadata.iter().find(|(key, _)| key.len() > u8::MAX.into()) {
+        return Err(EasypackError::InvalidMetadata(format!(
+            "Metadata key {key} is too big: only keys up to {} bytes are allowed",
+            u8::MAX
+        )));
+    }
+    Ok(())
+}
+
+/// The metadata, in the versions since 1.5, is the number of pairs, and for
+/// each one the length of the key, the key, the type of the value, the length
+/// of the value, and the value itself.
+/// This function returns the amount of bytes being written.
+/// # Errors
+/// Any IO error, or if the metadata can't be stored in the `ToC`.
+pub(super) fn write_metadata<W: Write>(w: &mut W, metadata: &Metadata) -> Result<usize> {
+    check_metadata(metadata)?;
+    let how_many: u16 = metadata.len().try_into()?;
+    w.write_all(&how_many.to_le_bytes())?;
+    let mut written = std::mem::size_of::<u16>();
+    for (key, value) in metadata.iter() {
+        let key_len: u8 = key.len().try_into()?;
+        let value_bytes = match value {
+            MetadataValue::U64(value) => value.to_le_bytes().to_vec(),
+            MetadataValue::Text(text) => text.as_bytes().to_vec(),
+        };
+        let value_len: u32 = value_bytes.len().try_into()?;
+        w.write_all(&key_len.to_le_bytes())?;
+        w.write_all(key.as_bytes())?;
+        w.write_all(&value.code().to_le_bytes())?;
+        w.write_all(&value_len.to_le_bytes())?;
+        w.write_all(&value_bytes)?;
+        written += std::mem::size_of::<u8>()
+            + key.len()
+            + std::mem::size_of::<u8>()
+            + std::mem::size_of::<u32>()
+            + value_bytes.len();
+    }
+    Ok(written)
+}
@@ -31,125 +31,61 @@ A list of
 */
 
 use std::io::Write;
-use std::marker::PhantomData;
 
+use super::packer::{self, PackFormat, TocEntry};
 use crate::error::{EasypackError, Result};
-use crate::utils;
-
-pub trait Steps {}
-
-macro_rules! writersteps {
-    ($name: tt) => {
-        pub struct $name {}
-        impl Steps for $name {}
-    };
-}
-
-writersteps!(NoneStep);
-writersteps!(HeaderStep);
-writersteps!(RecordStep);
-
-/// The packer, which can be used to pack data to the given writer.
-pub struct Packer<S: Steps, W: Write> {
-    pos: u32,
-    writer: W,
-    _step: PhantomData<S>,
-    toc: Vec<(u32, u32, String)>,
-}
-
-impl<W: Write> Packer<NoneStep, W> {
-    #[must_use]
-    /// Create a Packer, using the given writer.
-    pub const fn from_writer(writer: W) -> Packer<HeaderStep, W> {
-        Packer {
-            pos: 0,
-            writer,
-            _step: PhantomData,
-            toc: vec![],
+use crate::metadata::Metadata;
+
+/// The 1.0 version of the file format.
+pub struct Format;
+
+impl PackFormat for Format {
+    const VERSION: (u8, u8) = (1, 0);
+    const MAX_NAME_LEN: u64 = u8::MAX as u64;
+    const COMPRESSION: bool = false;
+    const RECORD_METADATA: bool = false;
+    const ARCHIVE_METADATA: bool = false;
+
+    fn write_toc<W: Write>(w: &mut W, toc: Vec<TocEntry>, _metadata: &Metadata) -> Result<u64> {
+        let mut written = 0;
+        for entry in toc {
+            let written_data: u64 = write_toc_entry(w, entry)?.try_into()?;
+            written += written_data;
         }
+        Ok(written)
     }
-}
 
-impl<W: Write> Packer<HeaderStep, W> {
-    /// Write the header of the file.
-    /// # Errors
-    /// Any IO error.
-    pub fn write_header(mut self) -> Result<Packer<RecordStep, W>> {
-        write_header(&mut self.writer)?;
-        let val: u32 = utils::HEADER_SIZE.try_into()?;
-        Ok(Packer {
-            pos: self.pos + val,
-            writer: self.writer,
-            _step: PhantomData,
-            toc: self.toc,
-        })
+    fn write_footer<W: Write>(
+        w: &mut W,
+        toc_position: u64,
+        how_many: u64,
+        _toc_checksum: u32,
+    ) -> Result<u64> {
+        let toc_position: u32 = toc_position.try_into()?;
+        let how_many: u32 = how_many.try_into()?;
+        w.write_all(&toc_position.to_le_bytes())?;
+        w.write_all(&how_many.to_le_bytes())?;
+        Ok((std::mem::size_of_val(&toc_position) + std::mem::size_of_val(&how_many)) as u64)
     }
 }
 
-impl<W: Write> Packer<RecordStep, W> {
-    /// Write a single record.
-    /// # Errors
-    /// In case the record's name is invalid, or the same as another already
-    /// inserted record.
-    pub fn write_record(&mut self, record: utils::Record) -> Result<()> {
-        let data_start = self.pos;
-        let data_len: u32 = record.data.len().try_into()?;
-        let data_end = self.pos + data_len;
-
-        write_record(&mut self.writer, &record.data)?;
-
-        if self.toc.iter().any(|r| r.2 == record.name) {
-            return Err(EasypackError::RecordSameName(format!(
-                "Name {} has already been used.",
-                record.name
-            )));
-        }
-        if record.name.len() > u8::MAX.into() {
-            return Err(EasypackError::RecordNameTooBig(
-                "Unable to write a record with name len > u8::MAX bytes.".into(),
-            ));
-        }
-        self.toc.push((data_start, data_len, record.name));
-        self.pos = data_end;
-        Ok(())
-    }
-
-    /// Write the toc, the footer, and consume the Packer.
-    /// # Errors
-    /// Any IO error.
-    pub fn close(mut self) -> Result<()> {
-        let table_pos = self.pos;
-        let mut how_many: u32 = 0;
-
-        for entry in &mut self.toc.drain(..) {
-            write_toc_entry(&mut self.writer, &entry)?;
-            how_many += 1;
-        }
-
-        self.writer.write_all(&table_pos.to_le_bytes())?;
-        self.writer.write_all(&how_many.to_le_bytes())?;
-
-        Ok(())
-    }
-}
+/// The packer writing the 1.0 version.
+pub type Packer<S, W> = packer::Packer<S, W, Format>;
 
+/// Write the header of the 1.0 version.
+/// # Errors
+/// Any IO error.
 pub fn write_header<W: Write>(w: &mut W) -> Result<()> {
-    w.write_all(utils::FILE_TYPE.as_bytes())?;
-    // Write version.
-    w.write_all(&1u8.to_le_bytes())?;
-    w.write_all(&0u8.to_le_bytes())?;
-    Ok(())
-}
-
-fn write_record<W: Write>(w: &mut W, data: &[u8]) -> Result<()> {
-    w.write_all(data)?;
-    Ok(())
+    packer::write_header(w, Format::VERSION)
 }
 
 // Toc contains the position in the file, the length of the string as u32, the
 // length of the string to be read, and and the bytes of the string itself.
-pub fn write_toc_entry<W: Write>(w: &mut W, toc_entry: &(u32, u32, String)) -> Result<()> {
-    let (pos, size, name) = toc_entry;
+// This function returns the amount of bytes being written.
+fn write_toc_entry<W: Write>(w: &mut W, toc_entry: TocEntry) -> Result<usize> {
+    let (pos, size, _, _, _, _, name) = toc_entry.extract();
+    let pos: u32 = pos.try_into()?;
+    let size: u32 = size.try_into()?;
     w.write_all(&pos.to_le_bytes())?;
     w.write_all(&size.to_le_bytes())?;
     if name.len() > u8::MAX.into() {
@@ -163,5 +99,9 @@ pub fn write_toc_entry<W: Write>(w: &mut W, toc_entry: &(u32, u32, String)) -> R
     // Checked above about this condition.
     w.write_all(&(name.len() as u8).to_le_bytes())?;
     w.write_all(&name.as_bytes()[..name.len()])?;
-    Ok(())
+    // This is the amount of bytes this function is writing.
+    Ok(std::mem::size_of::<u32>()
+        + std::mem::size_of::<u32>()
+        + std::mem::size_of::<u8>()
+        + name.len())
 }
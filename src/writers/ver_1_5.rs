@@ -0,0 +1,128 @@
+/*!
+# Packer 1.5 version.
+
+Same as the 1.4 version, but every record has its metadata (see `Metadata`),
+stored in the `ToC` as a list of typed key/value pairs.
+
+All numbers are written in little endian format.
+
+The structure of the packed file is as following:
+
+* HEADER
+
+- 4 bytes magic number
+- 1 byte for the major version
+- 1 byte for the minor version
+
+* RECORDS
+
+A list of records. the location in the file and the size to read is specified
+in the `ToC`
+
+* TOC (Table of Contents)
+
+A list of
+- u64 (8 bytes) position in the file
+- u64 (8 bytes) size of the content
+- u32 (4 bytes) CRC-32 of the content, as it is stored
+- u8 (1 byte) compression method of the content, see `Compression::code`
+- u64 (8 bytes) size of the content once decompressed
+- u16 (2 bytes) number of metadata pairs, and as many pairs of
+  - u8 (1 byte) size of the key
+  - as many bytes as specified above for the key
+  - u8 (1 byte) type of the value, see `MetadataValue::code`
+  - u32 (4 bytes) size of the value
+  - as many bytes as specified above for the value: a u64 (8 bytes), or
+    utf8 text
+- u8 (1 byte) size of the related name of the content
+- as many bytes as specified above for the name of the content
+
+* FOOTER
+
+- u64 (8 bytes) the position of the `ToC` table in the file
+- u64 (8 bytes) the number of records
+- u32 (4 bytes) CRC-32 of the whole `ToC`
+- u32 (4 bytes) CRC-32 of the 20 bytes above
+- 4 bytes magic number, marking the end of the footer
+
+*/
+
+use std::io::Write;
+
+use super::packer::{self, PackFormat, TocEntry};
+use crate::error::{EasypackError, Result};
+use crate::metadata::Metadata;
+
+/// The 1.5 version of the file format.
+pub struct Format;
+
+impl PackFormat for Format {
+    const VERSION: (u8, u8) = (1, 5);
+    const MAX_NAME_LEN: u64 = u8::MAX as u64;
+    const COMPRESSION: bool = true;
+    const RECORD_METADATA: bool = true;
+    const ARCHIVE_METADATA: bool = false;
+
+    fn write_toc<W: Write>(w: &mut W, toc: Vec<TocEntry>, _metadata: &Metadata) -> Result<u64> {
+        let mut written = 0;
+        for entry in toc {
+            let written_data: u64 = write_toc_entry(w, entry)?.try_into()?;
+            written += written_data;
+        }
+        Ok(written)
+    }
+
+    fn write_footer<W: Write>(
+        w: &mut W,
+        toc_position: u64,
+        how_many: u64,
+        toc_checksum: u32,
+    ) -> Result<u64> {
+        packer::write_checked_footer(w, toc_position, how_many, toc_checksum)
+    }
+}
+
+/// The packer writing the 1.5 version.
+pub type Packer<S, W> = packer::Packer<S, W, Format>;
+
+/// Write the header of the 1.5 version.
+/// # Errors
+/// Any IO error.
+pub fn write_header<W: Write>(w: &mut W) -> Result<()> {
+    packer::write_header(w, Format::VERSION)
+}
+
+/// `Toc` contains the position in the file, the length of the string as u64,
+/// the checksum of the data, the compression method, the length of the data
+/// once decompressed, the metadata, the length of the string to be read, and
+/// and the bytes of the string itself.
+/// This function returns the amount of bytes being written.
+fn write_toc_entry<W: Write>(w: &mut W, toc_entry: TocEntry) -> Result<usize> {
+    let (pos, size, checksum, compression, uncompressed_len, metadata, name) = toc_entry.extract();
+    w.write_all(&pos.to_le_bytes())?;
+    w.write_all(&size.to_le_bytes())?;
+    w.write_all(&checksum.to_le_bytes())?;
+    w.write_all(&compression.code().to_le_bytes())?;
+    w.write_all(&uncompressed_len.to_le_bytes())?;
+    let metadata_len = packer::write_metadata(w, &metadata)?;
+    if name.len() > u8::MAX.into() {
+        return Err(EasypackError::RecordNameTooBig(format!(
+            "Record name is too big: len is {}, while only names up to {} are allowed",
+            name.len(),
+            u8::MAX
+        )));
+    }
+    #[allow(clippy::pedantic)]
+    // Checked above about this condition.
+    w.write_all(&(name.len() as u8).to_le_bytes())?;
+    w.write_all(&name.as_bytes()[..name.len()])?;
+    // This is the amount of bytes this function is writing.
+    Ok(std::mem::size_of::<u64>()
+        + std::mem::size_of::<u64>()
+        + std::mem::size_of::<u32>()
+        + std::mem::size_of::<u8>()
+        + std::mem::size_of::<u64>()
+        + metadata_len
+        + std::mem::size_of::<u8>()
+        + name.len())
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 use assert_cmd::prelude::*; // Add methods on commands
 use predicates::prelude::*; // Used for writing assertions
 
@@ -125,3 +127,236 @@ fn test_complete_pack_unpack() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn verify_packed_file() -> Result<(), Box<dyn std::error::Error>> {
+    let binpath = std::path::PathBuf::from_str("verifyfile.bin")?;
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("pack")
+        .arg(binpath.as_path())
+        .arg("main")
+        .arg("src/main.rs");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("verify").arg(binpath.as_path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("verifyfile.bin: ok"));
+
+    // Corrupt the data of the record.
+    let mut data = std::fs::read(&binpath)?;
+    data[6] ^= 0x01;
+    std::fs::write(&binpath, data)?;
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("verify").arg(binpath.as_path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("record main has checksum"));
+
+    // Cleanup.
+    std::fs::remove_file(binpath)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `verifyfile.bin`: {}", e));
+
+    Ok(())
+}
+
+#[test]
+fn list_packed_file() -> Result<(), Box<dyn std::error::Error>> {
+    let binpath = std::path::PathBuf::from_str("listfile.bin")?;
+    let main_len = std::fs::metadata("src/main.rs")?.len();
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("pack")
+        .arg(binpath.as_path())
+        .arg("main")
+        .arg("src/main.rs")
+        .arg("lib")
+        .arg("src/lib.rs");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("list").arg(binpath.as_path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "main\t{} bytes\tat 6\n",
+            main_len
+        )))
+        .stdout(predicate::str::contains(format!(
+            "lib\t{} bytes\tat {}\n",
+            std::fs::metadata("src/lib.rs")?.len(),
+            6 + main_len
+        )));
+
+    // Cleanup.
+    std::fs::remove_file(binpath)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `listfile.bin`: {}", e));
+
+    Ok(())
+}
+
+#[test]
+fn extract_packed_file() -> Result<(), Box<dyn std::error::Error>> {
+    let binpath = std::path::PathBuf::from_str("extractfile.bin")?;
+    let outdir = std::path::PathBuf::from_str("extractdir")?;
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("pack")
+        .arg(binpath.as_path())
+        .arg("src/main.rs")
+        .arg("src/main.rs")
+        .arg("lib.rs")
+        .arg("src/lib.rs");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("extract")
+        .arg(binpath.as_path())
+        .arg(outdir.as_path());
+    cmd.assert().success();
+
+    let predicate_file = predicate::path::eq_file("src/main.rs");
+    assert!(predicate_file.eval(outdir.join("src/main.rs").as_path()));
+    let predicate_file = predicate::path::eq_file("src/lib.rs");
+    assert!(predicate_file.eval(outdir.join("lib.rs").as_path()));
+    // The modification time is kept, to the second.
+    let mtime = |path: &std::path::Path| -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(std::fs::metadata(path)?
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs())
+    };
+    assert_eq!(
+        mtime(outdir.join("lib.rs").as_path())?,
+        mtime("src/lib.rs".as_ref())?
+    );
+
+    // Cleanup.
+    std::fs::remove_file(binpath)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `extractfile.bin`: {}", e));
+    std::fs::remove_dir_all(outdir)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `extractdir`: {}", e));
+
+    Ok(())
+}
+
+#[test]
+fn extract_unsafe_name() -> Result<(), Box<dyn std::error::Error>> {
+    let binpath = std::path::PathBuf::from_str("extractunsafe.bin")?;
+    let outdir = std::path::PathBuf::from_str("extractunsafedir")?;
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("pack")
+        .arg(binpath.as_path())
+        .arg("../main.rs")
+        .arg("src/main.rs");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("extract")
+        .arg(binpath.as_path())
+        .arg(outdir.as_path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("UnsafeRecordName"));
+    assert!(!outdir.exists());
+
+    // Cleanup.
+    std::fs::remove_file(binpath)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `extractunsafe.bin`: {}", e));
+
+    Ok(())
+}
+
+#[test]
+fn pack_dir_extract() -> Result<(), Box<dyn std::error::Error>> {
+    let binpath = std::path::PathBuf::from_str("packdirfile.bin")?;
+    let outdir = std::path::PathBuf::from_str("packdirout")?;
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("pack-dir").arg(binpath.as_path()).arg("src");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("list").arg(binpath.as_path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("readers/mod.rs\t"));
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("extract")
+        .arg(binpath.as_path())
+        .arg(outdir.as_path());
+    cmd.assert().success();
+
+    let predicate_file = predicate::path::eq_file("src/readers/mod.rs");
+    assert!(predicate_file.eval(outdir.join("readers/mod.rs").as_path()));
+
+    // Cleanup.
+    std::fs::remove_file(binpath)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `packdirfile.bin`: {}", e));
+    std::fs::remove_dir_all(outdir)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `packdirout`: {}", e));
+
+    Ok(())
+}
+
+#[test]
+fn compact_packed_file() -> Result<(), Box<dyn std::error::Error>> {
+    let binpath = std::path::PathBuf::from_str("compactfile.bin")?;
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("pack")
+        .arg(binpath.as_path())
+        .arg("main")
+        .arg("src/main.rs");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("compact").arg(binpath.as_path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("0 bytes reclaimed\n"));
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("verify").arg(binpath.as_path());
+    cmd.assert().success();
+
+    // Cleanup.
+    std::fs::remove_file(binpath)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `compactfile.bin`: {}", e));
+
+    Ok(())
+}
+
+#[test]
+fn info_packed_file() -> Result<(), Box<dyn std::error::Error>> {
+    let binpath = std::path::PathBuf::from_str("infofile.bin")?;
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("pack")
+        .arg(binpath.as_path())
+        .arg("main.rs")
+        .arg("src/main.rs");
+    cmd.assert().success();
+
+    // Nothing to print, since the CLI does not set any metadata.
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("info").arg(binpath.as_path());
+    cmd.assert().success().stdout(predicate::str::is_empty());
+
+    let mut cmd = Command::cargo_bin("easypack")?;
+    cmd.arg("info");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Arguments must be `infile`"));
+
+    // Cleanup.
+    std::fs::remove_file(binpath)
+        .unwrap_or_else(|e| eprintln!("Unable to remove `infofile.bin`: {}", e));
+
+    Ok(())
+}